@@ -0,0 +1,17 @@
+#![no_main]
+//! Parses fuzzed token endpoint responses.
+use std::sync::Arc;
+
+use gcp_metadata_resolver::Detector;
+use gcp_metadata_resolver::test_util::FakeMetadataClient;
+use gcp_metadata_resolver_fuzz::block_on;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|body: String| {
+    let client = Arc::new(FakeMetadataClient::new([(
+        "instance/service-accounts/default/token",
+        body,
+    )]));
+    let detector = Detector::builder().metadata_client(client).build();
+    let _ = block_on(detector.access_token());
+});
@@ -0,0 +1,29 @@
+#![no_main]
+//! Runs resource and attribute detection against fuzzed metadata responses
+//! and environment variables.
+use std::sync::Arc;
+
+use gcp_metadata_resolver::Detector;
+use gcp_metadata_resolver_fuzz::{Environment, block_on};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|environment: Environment| {
+    let client = Arc::new(environment.metadata_client());
+    let mut builder = Detector::builder()
+        .metadata_client(client)
+        .env(environment.env_vars());
+    if let Some(location) = &environment.location {
+        builder = builder.location(location);
+    }
+    let detector = builder.build();
+    block_on(async {
+        if let Ok(resource) = detector.detect().await {
+            // Formatting walks every label.
+            let _ = resource.to_string();
+            let _ = resource.attributes();
+        }
+        if let Some(attributes) = detector.resource_attributes().await {
+            let _ = attributes.iter().count();
+        }
+    });
+});
@@ -0,0 +1,47 @@
+//! Shared harness for the fuzz targets.
+//!
+//! Fuzzed metadata responses are served through a [`FakeMetadataClient`], so
+//! every response the detector parses (zones, regions, `created-by`, tokens,
+//! ...) comes from the fuzzer.
+use std::future::Future;
+
+use arbitrary::Arbitrary;
+use gcp_metadata_resolver::test_util::{ENV_VARS, FakeMetadataClient, METADATA_PATHS};
+
+/// A fuzzed environment: a response (or `NotDefined`) for each metadata path,
+/// and a value (or nothing) for each environment variable.
+#[derive(Debug, Arbitrary)]
+pub struct Environment {
+    pub metadata: [Option<String>; METADATA_PATHS.len()],
+    pub env: [Option<String>; ENV_VARS.len()],
+    /// A tenant location, exercising zone/region splitting of arbitrary input.
+    pub location: Option<String>,
+}
+
+impl Environment {
+    pub fn env_vars(&self) -> impl Iterator<Item = (&'static str, String)> + '_ {
+        ENV_VARS
+            .iter()
+            .zip(&self.env)
+            .filter_map(|(key, value)| Some((*key, value.clone()?)))
+    }
+
+    /// Returns a client serving the fuzzed responses and reporting every
+    /// other path as not defined.
+    pub fn metadata_client(&self) -> FakeMetadataClient {
+        FakeMetadataClient::new(
+            METADATA_PATHS
+                .iter()
+                .zip(&self.metadata)
+                .filter_map(|(path, value)| Some((*path, value.clone()?))),
+        )
+    }
+}
+
+/// Runs `future` to completion on a single-threaded runtime.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
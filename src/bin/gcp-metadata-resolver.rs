@@ -0,0 +1,139 @@
+//! Prints the resource detected for the current GCP environment.
+//!
+//! ```text
+//! gcp-metadata-resolver [--json] [--explain] [--metadata-host <HOST>]
+//! ```
+//!
+//! `--json` prints the detected resource and its attributes as JSON.
+//! `--explain` probes the metadata server sequentially and logs every
+//! detection step to stderr.
+use std::process::ExitCode;
+
+use gcp_metadata_resolver::Detector;
+
+const USAGE: &str = "usage: gcp-metadata-resolver [--json] [--explain] [--metadata-host <HOST>]";
+
+struct Args {
+    help: bool,
+    json: bool,
+    explain: bool,
+    metadata_host: Option<String>,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = Self {
+            help: false,
+            json: false,
+            explain: false,
+            metadata_host: None,
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--json" => parsed.json = true,
+                "--explain" => parsed.explain = true,
+                "--metadata-host" => {
+                    let host = args.next().ok_or("--metadata-host requires a value")?;
+                    parsed.metadata_host = Some(host);
+                }
+                "-h" | "--help" => {
+                    parsed.help = true;
+                    break;
+                }
+                other => return Err(format!("unexpected argument `{other}`\n{USAGE}")),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+fn main() -> ExitCode {
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{message}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if args.help {
+        println!("{USAGE}");
+        return ExitCode::SUCCESS;
+    }
+    if args.explain {
+        tracing_subscriber::fmt()
+            .with_writer(std::io::stderr)
+            .without_time()
+            .init();
+    }
+
+    let mut builder = Detector::builder().sequential_debug(args.explain);
+    if let Some(host) = args.metadata_host {
+        builder = builder.metadata_host(host);
+    }
+    let detector = builder.build();
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("failed to build the tokio runtime");
+    runtime.block_on(async {
+        let resource = match detector.detect().await {
+            Ok(resource) => resource,
+            Err(err) => {
+                eprintln!("error: {err}");
+                return ExitCode::FAILURE;
+            }
+        };
+        let attributes = detector.resource_attributes().await;
+
+        if args.json {
+            let output = serde_json::json!({
+                "resource": resource,
+                "attributes": attributes,
+                "service_name": detector.service_name(),
+                "log_name": resource.suggested_log_name(),
+            });
+            println!("{output:#}");
+        } else {
+            println!("{resource}");
+            println!("  service.name={}", detector.service_name());
+            if let Some(log_name) = resource.suggested_log_name() {
+                println!("  log_name={log_name}");
+            }
+            for (key, value) in attributes.iter().flat_map(|attrs| attrs.iter()) {
+                println!("  {key}={value}");
+            }
+        }
+        ExitCode::SUCCESS
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        Args::parse(args.iter().map(|arg| (*arg).to_owned()))
+    }
+
+    #[test]
+    fn parses_flags() {
+        let args = parse(&["--json", "--metadata-host", "localhost:8080", "--explain"]).unwrap();
+        assert!(args.json);
+        assert!(args.explain);
+        assert_eq!(args.metadata_host.as_deref(), Some("localhost:8080"));
+        assert!(!args.help);
+    }
+
+    #[test]
+    fn parses_help() {
+        assert!(parse(&["--help"]).unwrap().help);
+        assert!(parse(&["--json", "-h", "--bogus"]).unwrap().help);
+    }
+
+    #[test]
+    fn rejects_unknown_and_incomplete_arguments() {
+        assert!(parse(&["--bogus"]).is_err());
+        assert!(parse(&["--metadata-host"]).is_err());
+    }
+}
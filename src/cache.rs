@@ -0,0 +1,221 @@
+//! A file cache for detection results, see [`DetectorBuilder::cache_file`](crate::DetectorBuilder::cache_file).
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::DetectedResource;
+use crate::detection::{ENV_VARS, ResourceAttributesGetter};
+use crate::metadata::MetadataClient;
+
+/// Version of the on-disk format; files with other versions are ignored.
+///
+/// Only bump this when a released format changes. Unreleased changes to the
+/// entry or its key ship as part of the current version.
+const FORMAT_VERSION: u32 = 1;
+
+/// Changes on every boot of a Linux kernel.
+const BOOT_ID_PATH: &str = "/proc/sys/kernel/random/boot_id";
+
+/// Identifies the machine, and the boot of it, a cached result was detected
+/// on, as well as the environment and overrides detection saw.
+///
+/// Including the boot ID invalidates caches on persistent disks when a VM is
+/// restarted, e.g. after being moved to a different zone or machine type.
+/// The inputs hash keeps workloads that share a machine apart, e.g. pods on
+/// the same GKE node, whose pod names come from the environment.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct CacheKey {
+    instance_id: String,
+    /// `None` where the kernel does not expose a boot ID.
+    boot_id: Option<String>,
+    /// Hash of the environment variables and scope detection uses.
+    inputs: u64,
+}
+
+impl CacheKey {
+    /// Builds the key with a single metadata request, or `None` if the
+    /// metadata server does not know the instance.
+    pub(crate) async fn current<C: MetadataClient>(
+        getter: &ResourceAttributesGetter<C>,
+    ) -> Option<Self> {
+        let instance_id = getter.metadata_instance_id().await?;
+        Some(Self {
+            instance_id,
+            boot_id: boot_id(Path::new(BOOT_ID_PATH)),
+            inputs: inputs_hash(getter),
+        })
+    }
+}
+
+/// Hashes the inputs of detection other than the metadata server with
+/// 64-bit FNV-1a, which, unlike `DefaultHasher`, is stable across builds.
+fn inputs_hash<C>(getter: &ResourceAttributesGetter<C>) -> u64 {
+    let env = ENV_VARS.map(|var| (getter.env_getter)(var).ok());
+    let scope = [&getter.scope.project_id, &getter.scope.location];
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for value in env.iter().chain(scope) {
+        let bytes = match value {
+            // Length-prefixed, so that adjacent values cannot run into each other.
+            Some(value) => [
+                &(value.len() as u64 + 1).to_le_bytes()[..],
+                value.as_bytes(),
+            ]
+            .concat(),
+            None => 0u64.to_le_bytes().to_vec(),
+        };
+        for byte in bytes {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+fn boot_id(path: &Path) -> Option<String> {
+    let boot_id = fs::read_to_string(path).ok()?;
+    let boot_id = boot_id.trim();
+    (!boot_id.is_empty()).then(|| boot_id.to_owned())
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    version: u32,
+    key: CacheKey,
+    resource: DetectedResource,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct FileCache {
+    path: PathBuf,
+}
+
+impl FileCache {
+    pub(crate) fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// Returns the cached resource if it was stored under `key`.
+    pub(crate) fn load(&self, key: &CacheKey) -> Option<DetectedResource> {
+        let contents = match fs::read(&self.path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return None,
+            Err(err) => {
+                tracing::warn!(?err, path = %self.path.display(), "Failed to read resource cache");
+                return None;
+            }
+        };
+        match serde_json::from_slice::<CacheEntry>(&contents) {
+            Ok(entry) if entry.version == FORMAT_VERSION && entry.key == *key => {
+                Some(entry.resource)
+            }
+            Ok(_) => {
+                tracing::debug!(path = %self.path.display(), "Resource cache is stale");
+                None
+            }
+            Err(err) => {
+                tracing::warn!(?err, path = %self.path.display(), "Failed to parse resource cache");
+                None
+            }
+        }
+    }
+
+    /// Stores `resource` under `key`. Failures are logged and otherwise ignored.
+    pub(crate) fn store(&self, key: CacheKey, resource: &DetectedResource) {
+        let entry = CacheEntry {
+            version: FORMAT_VERSION,
+            key,
+            resource: resource.clone(),
+        };
+        if let Err(err) = write_atomically(&self.path, &entry) {
+            tracing::warn!(?err, path = %self.path.display(), "Failed to write resource cache");
+        }
+    }
+}
+
+/// Writes to a sibling temporary file first so concurrent readers never see a
+/// partially written cache.
+fn write_atomically(path: &Path, entry: &CacheEntry) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(format!(".{}.tmp", std::process::id()));
+    fs::write(&tmp, serde_json::to_vec(entry)?)?;
+    fs::rename(&tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(boot_id: &str) -> CacheKey {
+        CacheKey {
+            instance_id: "1234567891".to_owned(),
+            boot_id: Some(boot_id.to_owned()),
+            inputs: 0,
+        }
+    }
+
+    #[test]
+    fn other_boot_invalidates_cache() {
+        let path = std::env::temp_dir().join(format!("gcp-boot-{}.json", std::process::id()));
+        let cache = FileCache::new(path.clone());
+        let resource = DetectedResource::ComputeEngine {
+            project_id: "my-project".to_owned(),
+            instance_id: Some("1234567891".to_owned()),
+            zone: Some("us-central1-a".to_owned()),
+        };
+        cache.store(key("first-boot"), &resource);
+        assert_eq!(cache.load(&key("first-boot")), Some(resource));
+        assert_eq!(cache.load(&key("second-boot")), None);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn other_format_version_is_ignored() {
+        let path = std::env::temp_dir().join(format!("gcp-version-{}.json", std::process::id()));
+        let cache = FileCache::new(path.clone());
+        let resource = DetectedResource::ComputeEngine {
+            project_id: "my-project".to_owned(),
+            instance_id: Some("1234567891".to_owned()),
+            zone: None,
+        };
+        cache.store(key("boot"), &resource);
+        let mut entry: serde_json::Value =
+            serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(entry["version"], 1);
+        assert_eq!(cache.load(&key("boot")), Some(resource));
+
+        entry["version"] = 2.into();
+        fs::write(&path, serde_json::to_vec(&entry).unwrap()).unwrap();
+        assert_eq!(cache.load(&key("boot")), None);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn reads_boot_id() {
+        let path = std::env::temp_dir().join(format!("gcp-boot-id-{}", std::process::id()));
+        fs::write(&path, "e694634e-18ec-4e31-8f60-262c8b2e80f2\n").unwrap();
+        assert_eq!(
+            boot_id(&path).as_deref(),
+            Some("e694634e-18ec-4e31-8f60-262c8b2e80f2")
+        );
+        fs::remove_file(&path).unwrap();
+        assert_eq!(boot_id(&path), None);
+    }
+
+    #[test]
+    fn inputs_hash_covers_env_and_scope() {
+        let pod = |name: &'static str| {
+            ResourceAttributesGetter::new((), move |var| match var {
+                "HOSTNAME" => Ok(name.to_owned()),
+                _ => Err(std::env::VarError::NotPresent),
+            })
+        };
+        assert_eq!(inputs_hash(&pod("pod-a")), inputs_hash(&pod("pod-a")));
+        assert_ne!(inputs_hash(&pod("pod-a")), inputs_hash(&pod("pod-b")));
+
+        let mut scoped = pod("pod-a");
+        scoped.scope.location = Some("europe-west1".to_owned());
+        assert_ne!(inputs_hash(&scoped), inputs_hash(&pod("pod-a")));
+    }
+}
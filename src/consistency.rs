@@ -0,0 +1,221 @@
+//! Checks of a detected resource against attributes configured elsewhere.
+//!
+//! Telemetry pipelines often set resource attributes in more than one place,
+//! e.g. `OTEL_RESOURCE_ATTRIBUTES` for traces and the detected resource for
+//! logs. When they disagree, signals of the same workload end up under
+//! different resources. [`DetectedResource::inconsistencies`] and
+//! [`GcpResourceAttributes::inconsistencies`] report such disagreements, and
+//! `Detector::env_inconsistencies` checks `OTEL_RESOURCE_ATTRIBUTES`.
+use std::collections::HashMap;
+use std::fmt;
+
+#[cfg(feature = "detector")]
+use crate::detection::ResourceAttributesGetter;
+use crate::{DetectedResource, GcpResourceAttributes};
+
+/// The environment variable of the [OpenTelemetry SDK configuration] with
+/// additional resource attributes.
+///
+/// [OpenTelemetry SDK configuration]: https://opentelemetry.io/docs/specs/otel/configuration/sdk-environment-variables/
+#[cfg(feature = "detector")]
+const RESOURCE_ATTRIBUTES_ENV: &str = "OTEL_RESOURCE_ATTRIBUTES";
+
+/// An attribute whose configured value differs from the detected one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Inconsistency {
+    /// The attribute key, e.g. `cloud.region`.
+    pub key: String,
+    /// The value detection produced.
+    pub detected: String,
+    /// The value found in the checked attributes.
+    pub found: String,
+}
+
+impl fmt::Display for Inconsistency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: detected {:?}, found {:?}",
+            self.key, self.detected, self.found
+        )
+    }
+}
+
+impl DetectedResource {
+    /// Compares the resource's [attributes](Self::attributes) with
+    /// `attributes`, e.g. those of an OpenTelemetry SDK `Resource`.
+    ///
+    /// Only keys present on both sides are compared; attributes detection
+    /// does not produce are never reported.
+    ///
+    /// ```
+    /// use gcp_metadata_resolver::DetectedResource;
+    ///
+    /// let resource = DetectedResource::ComputeEngine {
+    ///     project_id: "my-project".to_owned(),
+    ///     instance_id: None,
+    ///     zone: Some("us-central1-a".to_owned()),
+    /// };
+    /// let inconsistencies =
+    ///     resource.inconsistencies([("cloud.account.id", "other-project"), ("service.name", "api")]);
+    /// assert_eq!(
+    ///     inconsistencies[0].to_string(),
+    ///     r#"cloud.account.id: detected "my-project", found "other-project""#
+    /// );
+    /// assert_eq!(inconsistencies.len(), 1);
+    /// ```
+    pub fn inconsistencies<K, V>(
+        &self,
+        attributes: impl IntoIterator<Item = (K, V)>,
+    ) -> Vec<Inconsistency>
+    where
+        K: AsRef<str>,
+        V: fmt::Display,
+    {
+        compare(self.attributes(), attributes)
+    }
+}
+
+impl GcpResourceAttributes {
+    /// Compares the populated attributes with `attributes`.
+    /// See [`DetectedResource::inconsistencies`].
+    pub fn inconsistencies<K, V>(
+        &self,
+        attributes: impl IntoIterator<Item = (K, V)>,
+    ) -> Vec<Inconsistency>
+    where
+        K: AsRef<str>,
+        V: fmt::Display,
+    {
+        compare(self.iter(), attributes)
+    }
+}
+
+fn compare<'a, K, V>(
+    detected: impl IntoIterator<Item = (&'static str, &'a str)>,
+    attributes: impl IntoIterator<Item = (K, V)>,
+) -> Vec<Inconsistency>
+where
+    K: AsRef<str>,
+    V: fmt::Display,
+{
+    let detected: HashMap<_, _> = detected.into_iter().collect();
+    attributes
+        .into_iter()
+        .filter_map(|(key, value)| {
+            let key = key.as_ref();
+            let detected = *detected.get(key)?;
+            let found = value.to_string();
+            (detected != found).then(|| Inconsistency {
+                key: key.to_owned(),
+                detected: detected.to_owned(),
+                found,
+            })
+        })
+        .collect()
+}
+
+/// Returns the attributes configured in `OTEL_RESOURCE_ATTRIBUTES`, read
+/// through the detector's environment.
+#[cfg(feature = "detector")]
+pub(crate) fn env_attributes<C>(getter: &ResourceAttributesGetter<C>) -> Vec<(String, String)> {
+    (getter.env_getter)(RESOURCE_ATTRIBUTES_ENV)
+        .map(|value| parse_resource_attributes(&value))
+        .unwrap_or_default()
+}
+
+/// Parses the `key1=value1,key2=value2` format of `OTEL_RESOURCE_ATTRIBUTES`,
+/// with percent-encoded values. Malformed entries are skipped.
+#[cfg(feature = "detector")]
+fn parse_resource_attributes(value: &str) -> Vec<(String, String)> {
+    value
+        .split(',')
+        .filter_map(|pair| {
+            let (key, value) = pair.split_once('=')?;
+            let key = key.trim();
+            (!key.is_empty()).then(|| (key.to_owned(), percent_decode(value.trim())))
+        })
+        .collect()
+}
+
+#[cfg(feature = "detector")]
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attributes_inconsistencies() {
+        let attrs = GcpResourceAttributes {
+            cloud_account_id: "my-project".to_owned(),
+            cloud_platform: Some("gcp_cloud_run".to_owned()),
+            cloud_region: Some("us-east1".to_owned()),
+            cloud_availability_zone: None,
+            host_id: None,
+            host_name: None,
+            host_type: None,
+            gce_instance_name: None,
+            gce_instance_hostname: None,
+            gce_instance_group_manager_name: None,
+            gce_instance_group_manager_region: None,
+            gce_instance_group_manager_zone: None,
+            k8s_cluster_name: None,
+            faas_name: Some("my-service".to_owned()),
+            faas_version: None,
+            faas_instance: None,
+        };
+        let found = attrs.inconsistencies([
+            ("cloud.region", "europe-west1"),
+            ("cloud.account.id", "my-project"),
+            ("cloud.availability_zone", "europe-west1-b"),
+            ("faas.name", "other-service"),
+        ]);
+        assert_eq!(
+            found
+                .iter()
+                .map(|inconsistency| inconsistency.key.as_str())
+                .collect::<Vec<_>>(),
+            ["cloud.region", "faas.name"]
+        );
+        assert_eq!(found[0].detected, "us-east1");
+        assert_eq!(found[0].found, "europe-west1");
+    }
+
+    #[cfg(feature = "detector")]
+    #[test]
+    fn parses_resource_attributes_env() {
+        assert_eq!(
+            parse_resource_attributes(
+                "cloud.region=us-east1, k8s.cluster.name = my%20cluster,,invalid,=x"
+            ),
+            [
+                ("cloud.region".to_owned(), "us-east1".to_owned()),
+                ("k8s.cluster.name".to_owned(), "my cluster".to_owned()),
+            ]
+        );
+        assert_eq!(percent_decode("100%25%2x%"), "100%%2x%");
+    }
+}
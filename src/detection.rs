@@ -0,0 +1,1166 @@
+//! Platform detection against the metadata server and the environment.
+use std::env::VarError;
+use std::fs::File;
+use std::io::Read;
+use std::sync::{Arc, LazyLock, Mutex, PoisonError};
+use std::time::Instant;
+
+use crate::location::{split_location, zone_region};
+use crate::metadata::MetadataClient;
+use crate::{
+    CLOUD_PLATFORM_APP_ENGINE, CLOUD_PLATFORM_CLOUD_FUNCTIONS, CLOUD_PLATFORM_CLOUD_RUN,
+    CLOUD_PLATFORM_COMPUTE_ENGINE, CLOUD_PLATFORM_KUBERNETES_ENGINE, DetectError, DetectedResource,
+    GcpResourceAttributes, ProbeOutcome, ProbeTiming,
+};
+
+/// Awaits metadata probes concurrently like `tokio::join!`, or one after
+/// another in the order given when the getter runs in [sequential debug
+/// mode](crate::DetectorBuilder::sequential_debug).
+macro_rules! join_probes {
+    ($getter:expr, $($probe:expr),+ $(,)?) => {
+        if $getter.debug {
+            ($($probe.await,)+)
+        } else {
+            tokio::join!($($probe),+)
+        }
+    };
+}
+
+/// Logs a step of the detection logic in [sequential debug
+/// mode](crate::DetectorBuilder::sequential_debug).
+macro_rules! explain {
+    ($getter:expr, $($arg:tt)+) => {
+        if $getter.debug {
+            tracing::info!($($arg)+);
+        }
+    };
+}
+
+/// Detect the environment using the given getter
+pub(crate) async fn detect_resource<C: MetadataClient>(
+    getter: &ResourceAttributesGetter<C>,
+) -> Result<DetectedResource, DetectError> {
+    let mut resource = detect_platform_resource(getter).await?;
+    if let Some(location) = &getter.scope.location {
+        resource.set_location(location.clone());
+    }
+    Ok(resource)
+}
+
+async fn detect_platform_resource<C: MetadataClient>(
+    getter: &ResourceAttributesGetter<C>,
+) -> Result<DetectedResource, DetectError> {
+    if getter.is_metadata_active().await {
+        // Fast path
+        let product_name = system_product_name();
+        explain!(getter, "system product name: {product_name:?}");
+        match product_name.as_deref() {
+            Some("Google App Engine") => {
+                return detect_app_engine_resource(getter)
+                    .await
+                    .ok_or(DetectError::NoProjectId);
+            }
+            Some("Google Cloud Functions") => {
+                return detect_cloud_function_resource(getter)
+                    .await
+                    .ok_or(DetectError::NoProjectId);
+            }
+            _ => {}
+        }
+
+        if getter.is_app_engine() {
+            return detect_app_engine_resource(getter)
+                .await
+                .ok_or(DetectError::NoProjectId);
+        }
+        if getter.is_cloud_function() {
+            return detect_cloud_function_resource(getter)
+                .await
+                .ok_or(DetectError::NoProjectId);
+        }
+        if getter.is_cloud_run_service() {
+            return detect_cloud_run_service_resource(getter)
+                .await
+                .ok_or(DetectError::NoProjectId);
+        }
+        if getter.is_cloud_run_job() {
+            return detect_cloud_run_job_resource(getter)
+                .await
+                .ok_or(DetectError::NoProjectId);
+        }
+        if getter.is_kubernetes_engine().await {
+            return detect_kubernetes_resource(getter)
+                .await
+                .ok_or(DetectError::NoProjectId);
+        }
+        if getter.is_compute_engine().await {
+            return detect_compute_engine_resource(getter)
+                .await
+                .ok_or(DetectError::NoProjectId);
+        }
+    }
+    explain!(getter, "no platform matched");
+    Err(DetectError::DetectionFailed)
+}
+
+/// Reads resource type on the Linux-based environments such as
+// Cloud Functions, Cloud Run, GKE, GCE, GAE, etc.
+fn system_product_name() -> Option<String> {
+    #[cfg(not(target_os = "linux"))]
+    return None;
+
+    #[cfg(target_os = "linux")]
+    {
+        let path = "/sys/class/dmi/id/product_name";
+        Some(
+            File::open(path)
+                .and_then(|mut file| {
+                    let mut s = String::new();
+                    file.read_to_string(&mut s)?;
+                    Ok(s)
+                })
+                .unwrap_or_else(|_| String::new()),
+        )
+    }
+}
+
+pub(crate) struct ResourceAttributesGetter<C> {
+    /// A generic metadata client.
+    ///
+    /// You normally would use HttpMetadataClient.
+    pub(crate) metadata_client: C,
+    /// This is used to allow testing of environment variable getters, and to
+    /// run detection against a fixed environment.
+    pub(crate) env_getter: EnvGetter,
+    /// Tenant-provided values that take precedence over the metadata server.
+    pub(crate) scope: Scope,
+    /// Metadata requests made through this getter.
+    probes: Mutex<Vec<ProbeTiming>>,
+    /// Runs probes sequentially and explains every detection step.
+    pub(crate) debug: bool,
+}
+
+/// Values a scoped detector reports instead of the metadata server's.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Scope {
+    pub(crate) project_id: Option<String>,
+    pub(crate) location: Option<String>,
+}
+
+impl Scope {
+    /// Applies the overrides to a resource that was not detected through a
+    /// [`ResourceAttributesGetter`], e.g. one read from a resource file.
+    #[cfg_attr(not(feature = "file-source"), expect(dead_code))]
+    pub(crate) fn apply(&self, resource: &mut DetectedResource) {
+        if let Some(project_id) = &self.project_id {
+            resource.set_project_id(project_id.clone());
+        }
+        if let Some(location) = &self.location {
+            resource.set_location(location.clone());
+        }
+    }
+}
+
+/// The environment variables read during detection.
+#[cfg_attr(
+    not(any(feature = "file-cache", feature = "test-util")),
+    expect(dead_code)
+)]
+pub const ENV_VARS: [&str; 14] = [
+    "CLOUD_RUN_JOB",
+    "CONTAINER_NAME",
+    "FUNCTION_TARGET",
+    "GAE_INSTANCE",
+    "GAE_MODULE_NAME",
+    "GAE_SERVICE",
+    "GAE_VERSION",
+    "GOOGLE_CLOUD_PROJECT",
+    "HOSTNAME",
+    "K_CONFIGURATION",
+    "K_REVISION",
+    "K_SERVICE",
+    "KUBERNETES_SERVICE_HOST",
+    "NAMESPACE_NAME",
+];
+
+/// The metadata paths read during detection, relative to `computeMetadata/v1/`.
+#[cfg(any(test, feature = "test-util"))]
+pub const METADATA_PATHS: [&str; 14] = [
+    "",
+    "instance/attributes/cluster-location",
+    "instance/attributes/cluster-name",
+    "instance/attributes/created-by",
+    "instance/attributes/gae_app_bucket",
+    "instance/cpu-platform",
+    "instance/hostname",
+    "instance/id",
+    "instance/machine-type",
+    "instance/name",
+    "instance/preempted",
+    "instance/region",
+    "instance/zone",
+    "project/project-id",
+];
+
+/// Looks up an environment variable.
+type EnvGetter = Arc<dyn Fn(&str) -> Result<String, VarError> + Send + Sync>;
+
+impl<C> ResourceAttributesGetter<C> {
+    pub(crate) fn new(
+        metadata_client: C,
+        env_getter: impl Fn(&str) -> Result<String, VarError> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            metadata_client,
+            env_getter: Arc::new(env_getter),
+            scope: Scope::default(),
+            probes: Mutex::default(),
+            debug: false,
+        }
+    }
+
+    /// Returns a getter sharing the client, environment and scope, with its
+    /// own probe statistics. Used to instrument a single detection run.
+    pub(crate) fn session(&self) -> Self
+    where
+        C: Clone,
+    {
+        Self {
+            metadata_client: self.metadata_client.clone(),
+            env_getter: self.env_getter.clone(),
+            scope: self.scope.clone(),
+            probes: Mutex::default(),
+            debug: self.debug,
+        }
+    }
+
+    pub(crate) fn probes(&self) -> Vec<ProbeTiming> {
+        self.probes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+impl<C: MetadataClient> ResourceAttributesGetter<C> {
+    async fn metadata(&self, path: &str) -> Option<String> {
+        let start = Instant::now();
+        let result = self.metadata_client.resolve(path).await;
+        let probe = ProbeTiming {
+            path: path.to_owned(),
+            duration: start.elapsed(),
+            outcome: ProbeOutcome::of(&result),
+        };
+        explain!(
+            self,
+            "probe {path:?} -> {:?} in {:?}: {:?}",
+            probe.outcome,
+            probe.duration,
+            result.as_deref().map(str::trim),
+        );
+        self.probes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(probe);
+        match result {
+            Ok(body) => Some(body.trim().to_string()),
+            Err(err) => {
+                tracing::error!(?err, "Failed to get metadata from {}", path);
+                None
+            }
+        }
+    }
+
+    pub(crate) async fn metadata_project_id(&self) -> Option<String> {
+        if let Some(project_id) = &self.scope.project_id {
+            return Some(project_id.clone());
+        }
+        self.metadata("project/project-id").await
+    }
+
+    pub(crate) async fn metadata_instance_id(&self) -> Option<String> {
+        self.metadata("instance/id").await
+    }
+
+    async fn metadata_zone(&self) -> Option<String> {
+        let zone = self.metadata("instance/zone").await.unwrap_or_default();
+        if !zone.is_empty() {
+            return zone.rsplit_once('/').map(|(_, zone)| zone.to_owned());
+        }
+        None
+    }
+
+    async fn metadata_instance_name(&self) -> Option<String> {
+        self.metadata("instance/name").await
+    }
+
+    async fn metadata_instance_hostname(&self) -> Option<String> {
+        self.metadata("instance/hostname").await
+    }
+
+    async fn metadata_machine_type(&self) -> Option<String> {
+        self.metadata("instance/machine-type").await
+    }
+
+    async fn metadata_region(&self) -> Option<String> {
+        let region = self.metadata("instance/region").await.unwrap_or_default();
+        if !region.is_empty() {
+            return region.rsplit_once('/').map(|(_, region)| region.to_owned());
+        }
+        None
+    }
+
+    async fn is_metadata_active(&self) -> bool {
+        let active = self.metadata("").await.unwrap_or_default() != "";
+        explain!(self, "metadata server active: {active}");
+        active
+    }
+
+    fn is_app_engine(&self) -> bool {
+        let service = (self.env_getter)("GAE_SERVICE").unwrap_or_default();
+        let version = (self.env_getter)("GAE_VERSION").unwrap_or_default();
+        let instance = (self.env_getter)("GAE_INSTANCE").unwrap_or_default();
+        let is_app_engine = !service.is_empty() && !version.is_empty() && !instance.is_empty();
+        explain!(
+            self,
+            "App Engine: {is_app_engine} (GAE_SERVICE={service:?}, GAE_VERSION={version:?}, \
+             GAE_INSTANCE={instance:?}, all must be set)"
+        );
+        is_app_engine
+    }
+
+    fn is_cloud_function(&self) -> bool {
+        let is_cloud_function = (self.env_getter)("FUNCTION_TARGET").is_ok_and(|v| !v.is_empty());
+        explain!(
+            self,
+            "Cloud Functions: {is_cloud_function} (FUNCTION_TARGET must be set)"
+        );
+        is_cloud_function
+    }
+
+    fn is_cloud_run_service(&self) -> bool {
+        let has_config = (self.env_getter)("K_CONFIGURATION").is_ok_and(|v| !v.is_empty());
+        let has_function_target = (self.env_getter)("FUNCTION_TARGET").is_ok_and(|v| !v.is_empty());
+        let is_cloud_run_service = has_config && !has_function_target;
+        explain!(
+            self,
+            "Cloud Run service: {is_cloud_run_service} (K_CONFIGURATION set: {has_config}, \
+             FUNCTION_TARGET set: {has_function_target})"
+        );
+        is_cloud_run_service
+    }
+
+    fn is_cloud_run_job(&self) -> bool {
+        let is_cloud_run_job = (self.env_getter)("CLOUD_RUN_JOB").is_ok_and(|v| !v.is_empty());
+        explain!(
+            self,
+            "Cloud Run job: {is_cloud_run_job} (CLOUD_RUN_JOB must be set)"
+        );
+        is_cloud_run_job
+    }
+
+    async fn is_kubernetes_engine(&self) -> bool {
+        let cluster_name = self
+            .metadata("instance/attributes/cluster-name")
+            .await
+            .unwrap_or_default();
+        let is_kubernetes_engine = !cluster_name.is_empty();
+        explain!(
+            self,
+            "GKE: {is_kubernetes_engine} (instance/attributes/cluster-name must be set)"
+        );
+        is_kubernetes_engine
+    }
+
+    async fn is_compute_engine(&self) -> bool {
+        let (preempted, platform, app_bucket) = join_probes!(
+            self,
+            self.metadata("instance/preempted"),
+            self.metadata("instance/cpu-platform"),
+            self.metadata("instance/attributes/gae_app_bucket")
+        );
+        let is_compute_engine = preempted.unwrap_or_default() != ""
+            && platform.unwrap_or_default() != ""
+            && app_bucket.unwrap_or_default() == "";
+        explain!(
+            self,
+            "Compute Engine: {is_compute_engine} (instance/preempted and instance/cpu-platform \
+             must be set, instance/attributes/gae_app_bucket must not)"
+        );
+        is_compute_engine
+    }
+}
+
+async fn detect_app_engine_resource<C: MetadataClient>(
+    getter: &ResourceAttributesGetter<C>,
+) -> Option<DetectedResource> {
+    // We are not sure if the metadata service can return an empty string
+    // for project ID. Thus, we do some unergonormic string base work here.
+    let mut project_id = getter.metadata_project_id().await.unwrap_or_default();
+    if project_id.is_empty() {
+        project_id = (getter.env_getter)("GOOGLE_CLOUD_PROJECT").unwrap_or_default();
+    }
+    if project_id.is_empty() {
+        return None;
+    }
+    let zone = getter.metadata_zone().await;
+    let module_id = (getter.env_getter)("GAE_SERVICE")
+        .ok()
+        .or_else(|| (getter.env_getter)("GAE_MODULE_NAME").ok());
+    let version_id = (getter.env_getter)("GAE_VERSION").ok();
+
+    Some(DetectedResource::AppEngine {
+        project_id,
+        module_id,
+        version_id,
+        zone,
+    })
+}
+
+async fn detect_cloud_function_resource<C: MetadataClient>(
+    getter: &ResourceAttributesGetter<C>,
+) -> Option<DetectedResource> {
+    let project_id = getter.metadata_project_id().await.unwrap_or_default();
+    if project_id.is_empty() {
+        return None;
+    }
+    let region = getter.metadata_region().await;
+    // This used to be FUNCTION_NAME, but that seems to be legacy.
+    let function_name = (getter.env_getter)("K_SERVICE").ok();
+    Some(DetectedResource::CloudFunction {
+        project_id,
+        region,
+        function_name,
+    })
+}
+
+async fn detect_cloud_run_service_resource<C: MetadataClient>(
+    getter: &ResourceAttributesGetter<C>,
+) -> Option<DetectedResource> {
+    let project_id = getter.metadata_project_id().await.unwrap_or_default();
+    if project_id.is_empty() {
+        return None;
+    }
+    let location = getter.metadata_region().await;
+    let service_name = (getter.env_getter)("K_SERVICE").ok();
+    let revision_name = (getter.env_getter)("K_REVISION").ok();
+    let configuration_name = (getter.env_getter)("K_CONFIGURATION").ok();
+    Some(DetectedResource::CloudRunRevision {
+        project_id,
+        location,
+        service_name,
+        revision_name,
+        configuration_name,
+    })
+}
+
+async fn detect_cloud_run_job_resource<C: MetadataClient>(
+    getter: &ResourceAttributesGetter<C>,
+) -> Option<DetectedResource> {
+    let project_id = getter.metadata_project_id().await.unwrap_or_default();
+    if project_id.is_empty() {
+        return None;
+    }
+    let location = getter.metadata_region().await;
+    let job_name = (getter.env_getter)("CLOUD_RUN_JOB").ok();
+    Some(DetectedResource::CloudRunJob {
+        project_id,
+        location,
+        job_name,
+    })
+}
+
+async fn detect_kubernetes_resource<C: MetadataClient>(
+    getter: &ResourceAttributesGetter<C>,
+) -> Option<DetectedResource> {
+    let project_id = getter.metadata_project_id().await.unwrap_or_default();
+    if project_id.is_empty() {
+        return None;
+    }
+
+    let (cluster_name, location) = join_probes!(
+        getter,
+        getter.metadata("instance/attributes/cluster-name"),
+        getter.metadata("instance/attributes/cluster-location")
+    );
+    let mut namespace_name = File::open("/var/run/secrets/kubernetes.io/serviceaccount/namespace")
+        .and_then(|mut file| {
+            let mut s = String::new();
+            file.read_to_string(&mut s)?;
+            Ok(s)
+        })
+        .ok();
+    if namespace_name.as_deref() == Some("") {
+        // if automountServiceAccountToken is disabled allow to customize
+        // the namespace via environment
+        namespace_name = (getter.env_getter)("NAMESPACE_NAME").ok();
+    }
+    // note: if deployment customizes hostname, HOSTNAME envvar will have invalid content
+    let pod_name = (getter.env_getter)("HOSTNAME").ok();
+    // there is no way to derive container name from within container; use custom envvar if available
+    let container_name = (getter.env_getter)("CONTAINER_NAME").ok();
+    Some(DetectedResource::KubernetesEngine {
+        project_id,
+        cluster_name,
+        location,
+        namespace_name,
+        pod_name,
+        container_name,
+    })
+}
+
+async fn detect_compute_engine_resource<C: MetadataClient>(
+    getter: &ResourceAttributesGetter<C>,
+) -> Option<DetectedResource> {
+    let project_id = getter.metadata_project_id().await.unwrap_or_default();
+    if project_id.is_empty() {
+        return None;
+    }
+    let (instance_id, zone) = join_probes!(
+        getter,
+        getter.metadata("instance/id"),
+        getter.metadata_zone()
+    );
+    Some(DetectedResource::ComputeEngine {
+        project_id,
+        instance_id,
+        zone,
+    })
+}
+
+pub(crate) async fn detect_resource_attributes<C: MetadataClient>(
+    getter: &ResourceAttributesGetter<C>,
+) -> Option<GcpResourceAttributes> {
+    let mut attrs = detect_platform_attributes(getter).await?;
+    if let Some(location) = &getter.scope.location {
+        let (region, zone) = split_location(location);
+        attrs.cloud_region = Some(region.to_owned());
+        attrs.cloud_availability_zone = zone.map(str::to_owned);
+    }
+    Some(attrs)
+}
+
+async fn detect_platform_attributes<C: MetadataClient>(
+    getter: &ResourceAttributesGetter<C>,
+) -> Option<GcpResourceAttributes> {
+    if !getter.is_metadata_active().await {
+        return None;
+    }
+
+    let cloud_account_id = getter.metadata_project_id().await?;
+
+    let mut attrs = GcpResourceAttributes {
+        cloud_account_id,
+        cloud_platform: None,
+        cloud_region: None,
+        cloud_availability_zone: None,
+        host_id: None,
+        host_name: None,
+        host_type: None,
+        gce_instance_name: None,
+        gce_instance_hostname: None,
+        gce_instance_group_manager_name: None,
+        gce_instance_group_manager_region: None,
+        gce_instance_group_manager_zone: None,
+        k8s_cluster_name: None,
+        faas_name: None,
+        faas_version: None,
+        faas_instance: None,
+    };
+
+    // Fast path via system product name
+    match system_product_name().as_deref() {
+        Some("Google App Engine") => {
+            detect_app_engine_attrs(getter, &mut attrs).await;
+            return Some(attrs);
+        }
+        Some("Google Cloud Functions") => {
+            detect_cloud_function_attrs(getter, &mut attrs).await;
+            return Some(attrs);
+        }
+        _ => {}
+    }
+
+    if getter.is_app_engine() {
+        detect_app_engine_attrs(getter, &mut attrs).await;
+    } else if getter.is_cloud_function() {
+        detect_cloud_function_attrs(getter, &mut attrs).await;
+    } else if getter.is_cloud_run_service() {
+        attrs.cloud_platform = Some(CLOUD_PLATFORM_CLOUD_RUN.to_owned());
+        attrs.cloud_region = getter.metadata_region().await;
+        attrs.faas_name = (getter.env_getter)("K_SERVICE").ok();
+        attrs.faas_version = (getter.env_getter)("K_REVISION").ok();
+        attrs.faas_instance = getter.metadata_instance_id().await;
+    } else if getter.is_cloud_run_job() {
+        attrs.cloud_platform = Some(CLOUD_PLATFORM_CLOUD_RUN.to_owned());
+        attrs.cloud_region = getter.metadata_region().await;
+        attrs.faas_name = (getter.env_getter)("CLOUD_RUN_JOB").ok();
+        attrs.faas_instance = getter.metadata_instance_id().await;
+    } else if getter.is_kubernetes_engine().await {
+        detect_gce_attrs(getter, &mut attrs).await;
+        attrs.cloud_platform = Some(CLOUD_PLATFORM_KUBERNETES_ENGINE.to_owned());
+        attrs.k8s_cluster_name = getter.metadata("instance/attributes/cluster-name").await;
+        if let Some(location) = getter
+            .metadata("instance/attributes/cluster-location")
+            .await
+        {
+            let (region, zone) = split_location(&location);
+            attrs.cloud_region = Some(region.to_owned());
+            if let Some(zone) = zone {
+                attrs.cloud_availability_zone = Some(zone.to_owned());
+            }
+        }
+    } else if getter.is_compute_engine().await {
+        detect_gce_attrs(getter, &mut attrs).await;
+    }
+
+    Some(attrs)
+}
+
+async fn detect_gce_attrs<C: MetadataClient>(
+    getter: &ResourceAttributesGetter<C>,
+    attrs: &mut GcpResourceAttributes,
+) {
+    attrs.cloud_platform = Some(CLOUD_PLATFORM_COMPUTE_ENGINE.to_owned());
+
+    let (zone, host_id, instance_name, hostname, machine_type, created_by) = join_probes!(
+        getter,
+        getter.metadata_zone(),
+        getter.metadata_instance_id(),
+        getter.metadata_instance_name(),
+        getter.metadata_instance_hostname(),
+        getter.metadata_machine_type(),
+        getter.metadata("instance/attributes/created-by"),
+    );
+
+    if let Some(zone) = zone {
+        attrs.cloud_region = zone_region(&zone).map(str::to_owned);
+        attrs.cloud_availability_zone = Some(zone);
+    }
+
+    attrs.host_id = host_id;
+    attrs.host_name = instance_name.clone();
+    attrs.host_type = machine_type;
+    attrs.gce_instance_name = instance_name;
+    attrs.gce_instance_hostname = hostname;
+
+    if let Some(created_by) = created_by
+        && let Some(caps) = MIG_RE.captures(&created_by)
+    {
+        attrs.gce_instance_group_manager_name = Some(caps[3].to_owned());
+        match &caps[1] {
+            "zones" => attrs.gce_instance_group_manager_zone = Some(caps[2].to_owned()),
+            "regions" => attrs.gce_instance_group_manager_region = Some(caps[2].to_owned()),
+            _ => {}
+        }
+    }
+}
+
+async fn detect_app_engine_attrs<C: MetadataClient>(
+    getter: &ResourceAttributesGetter<C>,
+    attrs: &mut GcpResourceAttributes,
+) {
+    attrs.cloud_platform = Some(CLOUD_PLATFORM_APP_ENGINE.to_owned());
+    if let Some(zone) = getter.metadata_zone().await {
+        attrs.cloud_region = zone_region(&zone).map(str::to_owned);
+        attrs.cloud_availability_zone = Some(zone);
+    }
+    attrs.faas_name = (getter.env_getter)("GAE_SERVICE")
+        .ok()
+        .or_else(|| (getter.env_getter)("GAE_MODULE_NAME").ok());
+    attrs.faas_version = (getter.env_getter)("GAE_VERSION").ok();
+    attrs.faas_instance = (getter.env_getter)("GAE_INSTANCE").ok();
+}
+
+async fn detect_cloud_function_attrs<C: MetadataClient>(
+    getter: &ResourceAttributesGetter<C>,
+    attrs: &mut GcpResourceAttributes,
+) {
+    attrs.cloud_platform = Some(CLOUD_PLATFORM_CLOUD_FUNCTIONS.to_owned());
+    attrs.cloud_region = getter.metadata_region().await;
+    attrs.faas_name = (getter.env_getter)("K_SERVICE").ok();
+    attrs.faas_version = (getter.env_getter)("FUNCTION_TARGET").ok();
+    attrs.faas_instance = getter.metadata_instance_id().await;
+}
+
+static MIG_RE: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(r"^projects/[^/]+/(zones|regions)/([^/]+)/instanceGroupManagers/([^/]+)$")
+        .unwrap()
+});
+
+#[cfg(test)]
+mod tests {
+    //! Tests taken from the go SDK implementation.
+    use super::*;
+    use crate::Detector;
+    use crate::metadata;
+    use crate::test_util::FakeMetadataClient;
+
+    use std::collections::HashMap;
+    use std::env::VarError;
+
+    #[tokio::test]
+    async fn cloud_platform_gke() {
+        let getter = ResourceAttributesGetter::new(
+            fake_client(&[("instance/attributes/cluster-name", "my-cluster")]),
+            |_| Err(VarError::NotPresent),
+        );
+        let resource = detect_resource(&getter).await.unwrap();
+        assert!(matches!(
+            resource,
+            DetectedResource::KubernetesEngine { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn cloud_platform_k8s_not_gke() {
+        let getter = ResourceAttributesGetter::new(fake_client(&[]), |_| Err(VarError::NotPresent));
+        let resource = detect_resource(&getter).await.unwrap();
+        assert!(matches!(resource, DetectedResource::ComputeEngine { .. }));
+    }
+
+    #[tokio::test]
+    async fn cloud_platform_unknown() {
+        let getter = ResourceAttributesGetter::new(FakeMetadataClient::default(), |_| {
+            Err(VarError::NotPresent)
+        });
+        let result = detect_resource(&getter).await;
+        assert!(matches!(result, Err(DetectError::DetectionFailed)));
+    }
+
+    #[tokio::test]
+    async fn cloud_platform_gce() {
+        let getter = ResourceAttributesGetter::new(fake_client(&[]), |_| Err(VarError::NotPresent));
+        let resource = detect_resource(&getter).await.unwrap();
+        assert!(matches!(resource, DetectedResource::ComputeEngine { .. }));
+    }
+
+    #[tokio::test]
+    async fn cloud_platform_cloud_run() {
+        let getter = ResourceAttributesGetter::new(fake_client(&[]), |key| match key {
+            "K_CONFIGURATION" => Ok("my-config".into()),
+            "K_SERVICE" => Ok("my-service".into()),
+            _ => Err(VarError::NotPresent),
+        });
+        let resource = detect_resource(&getter).await.unwrap();
+        assert!(matches!(
+            resource,
+            DetectedResource::CloudRunRevision { service_name, .. } if service_name.as_deref() == Some("my-service")
+        ));
+    }
+
+    #[tokio::test]
+    async fn cloud_platform_cloud_run_jobs() {
+        let getter = ResourceAttributesGetter::new(fake_client(&[]), |key| match key {
+            "CLOUD_RUN_JOB" => Ok("my-job".into()),
+            _ => Err(VarError::NotPresent),
+        });
+        let resource = detect_resource(&getter).await.unwrap();
+        assert!(
+            matches!(resource, DetectedResource::CloudRunJob { job_name, .. } if job_name.as_deref() == Some("my-job"))
+        );
+    }
+
+    #[tokio::test]
+    async fn cloud_platform_cloud_functions() {
+        let getter = ResourceAttributesGetter::new(fake_client(&[]), |key| match key {
+            "FUNCTION_TARGET" => Ok("my-function".into()),
+            "K_SERVICE" => Ok("my-function".into()),
+            _ => Err(VarError::NotPresent),
+        });
+        let resource = detect_resource(&getter).await.unwrap();
+        assert!(
+            matches!(resource, DetectedResource::CloudFunction { function_name, .. } if function_name.as_deref() == Some("my-function"))
+        );
+    }
+
+    #[tokio::test]
+    async fn project_id() {
+        let getter = ResourceAttributesGetter::new(fake_client(&[]), |key| match key {
+            "K_CONFIGURATION" => Ok("my-config".into()),
+            _ => Err(VarError::NotPresent),
+        });
+        let resource = detect_resource(&getter).await.unwrap();
+        assert!(matches!(
+            resource,
+            DetectedResource::CloudRunRevision { project_id, .. } if project_id == "my-project"
+        ));
+    }
+
+    #[tokio::test]
+    async fn instance_id() {
+        let getter = ResourceAttributesGetter::new(fake_client(&[]), |key| match key {
+            "K_CONFIGURATION" => Ok("my-config".into()),
+            _ => Err(VarError::NotPresent),
+        });
+        let instance_id = getter.metadata_instance_id().await.unwrap();
+        assert_eq!(&instance_id, "1234567891");
+    }
+
+    #[tokio::test]
+    async fn project_id_err() {
+        let getter = ResourceAttributesGetter::new(FakeMetadataClient::default(), |_| {
+            Err(VarError::NotPresent)
+        });
+        let result = detect_resource(&getter).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn metadata_paths_cover_detection() {
+        let client = FakeMetadataClient::new(METADATA_PATHS.map(|path| (path, "value")));
+        let platforms: [&[(&str, &str)]; 5] = [
+            &[],
+            &[("FUNCTION_TARGET", "function"), ("K_SERVICE", "service")],
+            &[("K_CONFIGURATION", "config"), ("K_SERVICE", "service")],
+            &[("CLOUD_RUN_JOB", "job")],
+            &[("GAE_SERVICE", "service")],
+        ];
+        for env in platforms {
+            let env: HashMap<_, _> = env.iter().copied().collect();
+            let getter = ResourceAttributesGetter::new(client.clone(), move |key| {
+                env.get(key)
+                    .map(|value| (*value).to_owned())
+                    .ok_or(VarError::NotPresent)
+            });
+            let _ = detect_resource(&getter).await;
+            let _ = detect_resource_attributes(&getter).await;
+            for probe in getter.probes.lock().unwrap().iter() {
+                assert!(
+                    METADATA_PATHS.contains(&probe.path.as_str()),
+                    "{} is missing from METADATA_PATHS",
+                    probe.path
+                );
+            }
+        }
+    }
+
+    /// A GCE instance's metadata, with `extra` added or replacing values.
+    fn fake_client(extra: &[(&'static str, &'static str)]) -> FakeMetadataClient {
+        let metadata = [
+            ("", "ok"),
+            ("project/project-id", "my-project"),
+            ("instance/id", "1234567891"),
+            ("instance/zone", "projects/1234567890/zones/us-central1-a"),
+            ("instance/name", "my-instance"),
+            (
+                "instance/hostname",
+                "my-instance.us-central1-a.c.my-project.internal",
+            ),
+            (
+                "instance/machine-type",
+                "projects/1234567890/machineTypes/e2-medium",
+            ),
+            ("instance/preempted", "false"),
+            ("instance/cpu-platform", "Intel Broadwell"),
+        ];
+        FakeMetadataClient::new(metadata.iter().chain(extra).copied())
+    }
+
+    #[tokio::test]
+    async fn resource_attributes_gce() {
+        let getter = ResourceAttributesGetter::new(fake_client(&[]), |_| Err(VarError::NotPresent));
+        let attrs = detect_resource_attributes(&getter).await.unwrap();
+        assert_eq!(attrs.cloud_account_id, "my-project");
+        assert_eq!(attrs.cloud_platform.as_deref(), Some("gcp_compute_engine"));
+        assert_eq!(
+            attrs.cloud_availability_zone.as_deref(),
+            Some("us-central1-a")
+        );
+        assert_eq!(attrs.cloud_region.as_deref(), Some("us-central1"));
+        assert_eq!(attrs.host_id.as_deref(), Some("1234567891"));
+        assert_eq!(attrs.host_name.as_deref(), Some("my-instance"));
+        assert_eq!(
+            attrs.host_type.as_deref(),
+            Some("projects/1234567890/machineTypes/e2-medium")
+        );
+        assert_eq!(attrs.gce_instance_name.as_deref(), Some("my-instance"));
+        assert_eq!(
+            attrs.gce_instance_hostname.as_deref(),
+            Some("my-instance.us-central1-a.c.my-project.internal")
+        );
+        assert_eq!(attrs.k8s_cluster_name, None);
+        assert_eq!(attrs.faas_name, None);
+    }
+
+    #[tokio::test]
+    async fn resource_attributes_gce_with_unusual_zone() {
+        let getter = ResourceAttributesGetter::new(
+            fake_client(&[("instance/zone", "projects/1234567890/zones/us-east1-B")]),
+            |_| Err(VarError::NotPresent),
+        );
+        let attrs = detect_resource_attributes(&getter).await.unwrap();
+        assert_eq!(attrs.cloud_availability_zone.as_deref(), Some("us-east1-B"));
+        assert_eq!(attrs.cloud_region.as_deref(), Some("us-east1"));
+    }
+
+    #[tokio::test]
+    async fn resource_attributes_gce_with_mig_zonal() {
+        let getter = ResourceAttributesGetter::new(
+            fake_client(&[(
+                "instance/attributes/created-by",
+                "projects/my-project/zones/us-central1-a/instanceGroupManagers/my-mig",
+            )]),
+            |_| Err(VarError::NotPresent),
+        );
+        let attrs = detect_resource_attributes(&getter).await.unwrap();
+        assert_eq!(
+            attrs.gce_instance_group_manager_name.as_deref(),
+            Some("my-mig")
+        );
+        assert_eq!(
+            attrs.gce_instance_group_manager_zone.as_deref(),
+            Some("us-central1-a")
+        );
+        assert_eq!(attrs.gce_instance_group_manager_region, None);
+    }
+
+    #[tokio::test]
+    async fn resource_attributes_gce_with_mig_regional() {
+        let getter = ResourceAttributesGetter::new(
+            fake_client(&[(
+                "instance/attributes/created-by",
+                "projects/my-project/regions/us-central1/instanceGroupManagers/my-rmig",
+            )]),
+            |_| Err(VarError::NotPresent),
+        );
+        let attrs = detect_resource_attributes(&getter).await.unwrap();
+        assert_eq!(
+            attrs.gce_instance_group_manager_name.as_deref(),
+            Some("my-rmig")
+        );
+        assert_eq!(
+            attrs.gce_instance_group_manager_region.as_deref(),
+            Some("us-central1")
+        );
+        assert_eq!(attrs.gce_instance_group_manager_zone, None);
+    }
+
+    #[tokio::test]
+    async fn resource_attributes_gke() {
+        let getter = ResourceAttributesGetter::new(
+            fake_client(&[
+                ("instance/attributes/cluster-name", "my-cluster"),
+                ("instance/attributes/cluster-location", "us-central1"),
+            ]),
+            |_| Err(VarError::NotPresent),
+        );
+        let attrs = detect_resource_attributes(&getter).await.unwrap();
+        assert_eq!(
+            attrs.cloud_platform.as_deref(),
+            Some("gcp_kubernetes_engine")
+        );
+        assert_eq!(attrs.cloud_region.as_deref(), Some("us-central1"));
+        // cluster-location is regional, but the instance still has a zone
+        assert_eq!(
+            attrs.cloud_availability_zone.as_deref(),
+            Some("us-central1-a")
+        );
+        assert_eq!(attrs.k8s_cluster_name.as_deref(), Some("my-cluster"));
+        assert_eq!(attrs.host_id.as_deref(), Some("1234567891"));
+        assert_eq!(attrs.host_name.as_deref(), Some("my-instance"));
+    }
+
+    #[tokio::test]
+    async fn resource_attributes_gke_zonal() {
+        let getter = ResourceAttributesGetter::new(
+            fake_client(&[
+                ("instance/attributes/cluster-name", "my-cluster"),
+                ("instance/attributes/cluster-location", "us-central1-a"),
+            ]),
+            |_| Err(VarError::NotPresent),
+        );
+        let attrs = detect_resource_attributes(&getter).await.unwrap();
+        assert_eq!(
+            attrs.cloud_platform.as_deref(),
+            Some("gcp_kubernetes_engine")
+        );
+        assert_eq!(attrs.cloud_region.as_deref(), Some("us-central1"));
+        assert_eq!(
+            attrs.cloud_availability_zone.as_deref(),
+            Some("us-central1-a")
+        );
+    }
+
+    #[tokio::test]
+    async fn resource_attributes_gke_unusual_location() {
+        let getter = ResourceAttributesGetter::new(
+            fake_client(&[
+                ("instance/attributes/cluster-name", "my-cluster"),
+                ("instance/attributes/cluster-location", "us-central1-X"),
+            ]),
+            |_| Err(VarError::NotPresent),
+        );
+        let attrs = detect_resource_attributes(&getter).await.unwrap();
+        assert_eq!(attrs.cloud_region.as_deref(), Some("us-central1"));
+        assert_eq!(
+            attrs.cloud_availability_zone.as_deref(),
+            Some("us-central1-X")
+        );
+    }
+
+    #[tokio::test]
+    async fn resource_attributes_cloud_run() {
+        let getter = ResourceAttributesGetter::new(
+            fake_client(&[("instance/region", "projects/123/regions/us-east1")]),
+            |key| match key {
+                "K_CONFIGURATION" => Ok("my-config".into()),
+                "K_SERVICE" => Ok("my-service".into()),
+                "K_REVISION" => Ok("my-service-00001".into()),
+                _ => Err(VarError::NotPresent),
+            },
+        );
+        let attrs = detect_resource_attributes(&getter).await.unwrap();
+        assert_eq!(attrs.cloud_platform.as_deref(), Some("gcp_cloud_run"));
+        assert_eq!(attrs.cloud_region.as_deref(), Some("us-east1"));
+        assert_eq!(attrs.faas_name.as_deref(), Some("my-service"));
+        assert_eq!(attrs.faas_version.as_deref(), Some("my-service-00001"));
+        assert_eq!(attrs.faas_instance.as_deref(), Some("1234567891"));
+        assert_eq!(attrs.host_id, None);
+    }
+
+    #[tokio::test]
+    async fn resource_attributes_cloud_run_job() {
+        let getter = ResourceAttributesGetter::new(
+            fake_client(&[("instance/region", "projects/123/regions/us-west1")]),
+            |key| match key {
+                "CLOUD_RUN_JOB" => Ok("my-job".into()),
+                _ => Err(VarError::NotPresent),
+            },
+        );
+        let attrs = detect_resource_attributes(&getter).await.unwrap();
+        assert_eq!(attrs.cloud_platform.as_deref(), Some("gcp_cloud_run"));
+        assert_eq!(attrs.faas_name.as_deref(), Some("my-job"));
+        assert_eq!(attrs.faas_version, None);
+        assert_eq!(attrs.faas_instance.as_deref(), Some("1234567891"));
+    }
+
+    #[tokio::test]
+    async fn scoped_project_and_location() {
+        let mut getter =
+            ResourceAttributesGetter::new(fake_client(&[]), |_| Err(VarError::NotPresent));
+        getter.scope = Scope {
+            project_id: Some("tenant-project".to_owned()),
+            location: Some("europe-west1".to_owned()),
+        };
+        let resource = detect_resource(&getter).await.unwrap();
+        assert_eq!(
+            resource,
+            DetectedResource::ComputeEngine {
+                project_id: "tenant-project".to_owned(),
+                instance_id: Some("1234567891".to_owned()),
+                zone: None,
+            }
+        );
+        let attrs = detect_resource_attributes(&getter).await.unwrap();
+        assert_eq!(attrs.cloud_account_id, "tenant-project");
+        assert_eq!(attrs.cloud_region.as_deref(), Some("europe-west1"));
+        assert_eq!(attrs.cloud_availability_zone, None);
+        // Instance-level attributes still come from the metadata server.
+        assert_eq!(attrs.host_id.as_deref(), Some("1234567891"));
+    }
+
+    #[tokio::test]
+    async fn scoped_zone() {
+        let mut getter =
+            ResourceAttributesGetter::new(fake_client(&[]), |_| Err(VarError::NotPresent));
+        getter.scope.location = Some("europe-west1-c".to_owned());
+        let resource = detect_resource(&getter).await.unwrap();
+        assert!(matches!(
+            resource,
+            DetectedResource::ComputeEngine { zone: Some(ref zone), .. } if zone == "europe-west1-c"
+        ));
+        let attrs = detect_resource_attributes(&getter).await.unwrap();
+        assert_eq!(attrs.cloud_region.as_deref(), Some("europe-west1"));
+        assert_eq!(
+            attrs.cloud_availability_zone.as_deref(),
+            Some("europe-west1-c")
+        );
+    }
+
+    #[tokio::test]
+    async fn scoped_zone_on_regional_platform() {
+        let mut getter = ResourceAttributesGetter::new(
+            fake_client(&[("instance/region", "projects/123/regions/us-west1")]),
+            |key| match key {
+                "K_CONFIGURATION" => Ok("my-config".into()),
+                "K_SERVICE" => Ok("my-service".into()),
+                "K_REVISION" => Ok("my-revision".into()),
+                _ => Err(VarError::NotPresent),
+            },
+        );
+        getter.scope.location = Some("europe-west1-c".to_owned());
+        let resource = detect_resource(&getter).await.unwrap();
+        assert!(matches!(
+            resource,
+            DetectedResource::CloudRunRevision { location: Some(ref location), .. }
+                if location == "europe-west1"
+        ));
+    }
+
+    #[tokio::test]
+    async fn detector_with_dyn_metadata_client() {
+        let client: Arc<dyn metadata::DynMetadataClient> = Arc::new(fake_client(&[(
+            "instance/attributes/cluster-name",
+            "my-cluster",
+        )]));
+        let detector = Detector::builder()
+            .metadata_client(client)
+            .env([("HOSTNAME", "my-pod")])
+            .build();
+        let resource = detector.detect().await.unwrap();
+        assert!(matches!(
+            resource,
+            DetectedResource::KubernetesEngine { cluster_name, pod_name, .. }
+                if cluster_name.as_deref() == Some("my-cluster") && pod_name.as_deref() == Some("my-pod")
+        ));
+    }
+
+    #[tokio::test]
+    async fn probes_are_counted() {
+        let getter = ResourceAttributesGetter::new(fake_client(&[]), |_| Err(VarError::NotPresent));
+        detect_resource(&getter).await.unwrap();
+        let probes = getter.probes();
+        // metadata active, cluster-name, 3 GCE checks, project, instance id and zone
+        assert_eq!(probes.len(), 8);
+        assert_eq!(probes[0].path, "");
+        assert_eq!(probes[0].outcome, ProbeOutcome::Found);
+        assert_eq!(probes[1].path, "instance/attributes/cluster-name");
+        assert_eq!(probes[1].outcome, ProbeOutcome::NotDefined);
+        assert!(getter.session().probes().is_empty());
+    }
+
+    #[tokio::test]
+    async fn sequential_debug_probes_in_order() {
+        let mut getter =
+            ResourceAttributesGetter::new(fake_client(&[]), |_| Err(VarError::NotPresent));
+        getter.debug = true;
+        detect_resource(&getter).await.unwrap();
+        let paths: Vec<_> = getter
+            .probes()
+            .into_iter()
+            .map(|probe| probe.path)
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                "",
+                "instance/attributes/cluster-name",
+                "instance/preempted",
+                "instance/cpu-platform",
+                "instance/attributes/gae_app_bucket",
+                "project/project-id",
+                "instance/id",
+                "instance/zone",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn resource_attributes_no_metadata() {
+        let getter = ResourceAttributesGetter::new(FakeMetadataClient::default(), |_| {
+            Err(VarError::NotPresent)
+        });
+        assert!(detect_resource_attributes(&getter).await.is_none());
+    }
+}
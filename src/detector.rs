@@ -0,0 +1,1147 @@
+//! A configurable resource detector and the process-wide global instance.
+use std::collections::HashMap;
+use std::env::{self, VarError};
+use std::fmt;
+#[cfg(any(feature = "file-cache", feature = "file-source"))]
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+use std::time::{Duration, Instant};
+
+use async_once_cell::OnceCell;
+#[cfg(feature = "integrations")]
+use opentelemetry_stackdriver::MonitoredResource;
+use thiserror::Error;
+use tracing::{Instrument, Span, field};
+
+#[cfg(feature = "file-cache")]
+use crate::cache::{CacheKey, FileCache};
+use crate::detection::{
+    ResourceAttributesGetter, Scope, detect_resource, detect_resource_attributes,
+};
+#[cfg(feature = "file-source")]
+use crate::file_source::{FileSourcePriority, ResourceFile};
+#[cfg(feature = "token")]
+use crate::metadata::{self, AccessToken, MetadataClient};
+use crate::metadata::{DynMetadataClient, HttpMetadataClient};
+use crate::{
+    DetectError, DetectedResource, DetectionSource, Diagnostics, GcpResourceAttributes,
+    Inconsistency, Initialized,
+};
+use crate::{consistency, service};
+
+/// Detects and caches the resource for the current GCP environment.
+///
+/// A `Detector` owns its metadata client, configuration, and caches. Any
+/// number of independent detectors can exist side by side, for example one
+/// per test, or one per environment a telemetry router impersonates. The free
+/// functions of this crate ([`detect`](crate::detect),
+/// [`resource_attributes`](crate::resource_attributes), ...) use the global
+/// detector, which can be configured once via [`set_global_detector`].
+///
+/// Each detection runs inside a single `INFO` span, `gcp_resource_detection`
+/// (or `gcp_resource_attributes_detection`), recording the detected
+/// `resource_type`, the number of metadata `probes`, the time spent in them
+/// (`probe_time_ms`), the total `duration_ms`, and the `outcome`.
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// let detector = gcp_metadata_resolver::Detector::builder()
+///     .request_timeout(Duration::from_secs(1))
+///     .build();
+/// if gcp_metadata_resolver::set_global_detector(detector).is_err() {
+///     // The global detector was already set or used.
+/// }
+/// ```
+pub struct Detector {
+    getter: ResourceAttributesGetter<Arc<dyn DynMetadataClient>>,
+    log_summary: bool,
+    #[cfg(feature = "file-cache")]
+    cache: Option<FileCache>,
+    #[cfg(feature = "file-source")]
+    resource_file: Option<ResourceFile>,
+    detected: OnceCell<DetectedResource>,
+    #[cfg(feature = "integrations")]
+    resource: OnceLock<Arc<MonitoredResource>>,
+    attributes: OnceCell<Option<GcpResourceAttributes>>,
+    diagnostics: Mutex<Option<Diagnostics>>,
+    attributes_diagnostics: OnceLock<Diagnostics>,
+}
+
+impl Detector {
+    /// Returns a builder for a new detector.
+    pub fn builder() -> DetectorBuilder {
+        DetectorBuilder::default()
+    }
+
+    /// Returns a new detector scoped to a tenant's project and location.
+    ///
+    /// The scoped detector shares this detector's metadata client and
+    /// environment, so instance-level labels (instance ID, service name, ...)
+    /// still come from the real metadata server, while `project_id` and
+    /// `location` replace the values it would report. It has its own caches.
+    ///
+    /// `location` is reported as the resource's zone, region or location,
+    /// whichever the detected platform uses. A region is never reported as a
+    /// zone: on App Engine and Compute Engine it leaves the zone unset. On
+    /// regional platforms (Cloud Run, Cloud Functions) a zone is reported as
+    /// its region. GKE clusters report the value as-is.
+    ///
+    /// Scoped detectors never use the cache file (`DetectorBuilder::cache_file`).
+    /// A resource file (`DetectorBuilder::resource_file`) is still read, with
+    /// the scope applied on top.
+    pub fn scoped(&self, project_id: impl Into<String>, location: impl Into<String>) -> Detector {
+        let mut getter = self.getter.session();
+        getter.scope = Scope {
+            project_id: Some(project_id.into()),
+            location: Some(location.into()),
+        };
+        let detector = Detector::from_getter(getter, self.log_summary);
+        #[cfg(feature = "file-source")]
+        let detector = Detector {
+            resource_file: self.resource_file.clone(),
+            ..detector
+        };
+        detector
+    }
+
+    fn from_getter(
+        getter: ResourceAttributesGetter<Arc<dyn DynMetadataClient>>,
+        log_summary: bool,
+    ) -> Self {
+        Self {
+            getter,
+            log_summary,
+            #[cfg(feature = "file-cache")]
+            cache: None,
+            #[cfg(feature = "file-source")]
+            resource_file: None,
+            detected: OnceCell::new(),
+            #[cfg(feature = "integrations")]
+            resource: OnceLock::new(),
+            attributes: OnceCell::new(),
+            diagnostics: Mutex::new(None),
+            attributes_diagnostics: OnceLock::new(),
+        }
+    }
+
+    /// Eagerly runs resource detection. See [`init`](crate::init).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DetectError`] if the metadata server is unreachable or the
+    /// platform could not be identified.
+    pub async fn init(&self) -> Result<Initialized<'_>, DetectError> {
+        let start = Instant::now();
+        let mut performed_detection = false;
+        let resource = self
+            .detected
+            .get_or_try_init(async {
+                performed_detection = true;
+                self.run_detection().await
+            })
+            .await?;
+        #[cfg(feature = "integrations")]
+        self.monitored_resource(resource);
+        Ok(Initialized {
+            resource,
+            performed_detection,
+            elapsed: start.elapsed(),
+        })
+    }
+
+    /// Detects the [`DetectedResource`]. See [`detect`](crate::detect).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DetectError`] if the metadata server is unreachable or the
+    /// platform could not be identified.
+    pub async fn detect(&self) -> Result<&DetectedResource, DetectError> {
+        self.detected.get_or_try_init(self.run_detection()).await
+    }
+
+    /// Detects the [`MonitoredResource`]. See [`detected_resource`](crate::detected_resource).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DetectError`] if the metadata server is unreachable or the
+    /// platform could not be identified.
+    #[cfg(feature = "integrations")]
+    pub async fn detected_resource(&self) -> Result<&MonitoredResource, DetectError> {
+        self.resource_cell().await.map(Arc::as_ref)
+    }
+
+    /// Like [`detected_resource`](Self::detected_resource), but returns a shared handle.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DetectError`] if the metadata server is unreachable or the
+    /// platform could not be identified.
+    #[cfg(feature = "integrations")]
+    pub async fn detected_resource_arc(&self) -> Result<Arc<MonitoredResource>, DetectError> {
+        self.resource_cell().await.map(Arc::clone)
+    }
+
+    /// Returns the cached [`DetectedResource`] without awaiting.
+    /// See [`try_detect`](crate::try_detect).
+    pub fn try_detect(&self) -> Option<&DetectedResource> {
+        self.detected.get()
+    }
+
+    /// Returns the cached [`MonitoredResource`] without awaiting.
+    /// See [`try_detected_resource`](crate::try_detected_resource).
+    #[cfg(feature = "integrations")]
+    pub fn try_detected_resource(&self) -> Option<&MonitoredResource> {
+        let detected = self.try_detect()?;
+        Some(self.monitored_resource(detected))
+    }
+
+    /// Detects the resource attributes. See [`resource_attributes`](crate::resource_attributes).
+    ///
+    /// Always queries the metadata server; a configured resource file
+    /// (`DetectorBuilder::resource_file`) is not read.
+    pub async fn resource_attributes(&self) -> Option<&GcpResourceAttributes> {
+        self.attributes
+            .get_or_init(self.run_attributes_detection())
+            .await
+            .as_ref()
+    }
+
+    /// Returns per-probe and total timings of resource detection.
+    ///
+    /// Returns `None` until [`detect`](Self::detect) (or one of the methods
+    /// built on it) has completed. Reports the most recent run, so a failed
+    /// detection is reported until a retry replaces it.
+    pub fn diagnostics(&self) -> Option<Diagnostics> {
+        self.diagnostics
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Like [`diagnostics`](Self::diagnostics), for
+    /// [`resource_attributes`](Self::resource_attributes).
+    pub fn attributes_diagnostics(&self) -> Option<&Diagnostics> {
+        self.attributes_diagnostics.get()
+    }
+
+    /// Returns the GCP project ID, or `None` if unavailable.
+    pub async fn project_id(&self) -> Option<String> {
+        self.getter.metadata_project_id().await
+    }
+
+    /// Returns the GCE instance ID, or `None` if unavailable.
+    pub async fn instance_id(&self) -> Option<String> {
+        self.getter.metadata_instance_id().await
+    }
+
+    /// Infers a [`service.name`] for the workload from its environment.
+    /// See [`service_name`](crate::service_name).
+    ///
+    /// [`service.name`]: https://opentelemetry.io/docs/specs/semconv/resource/#service
+    pub fn service_name(&self) -> String {
+        service::service_name(&self.getter)
+    }
+
+    /// Compares the detected resource with the attributes configured in
+    /// `OTEL_RESOURCE_ATTRIBUTES`, read through the detector's
+    /// [environment](DetectorBuilder::env). See
+    /// [`DetectedResource::inconsistencies`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DetectError`] if the metadata server is unreachable or the
+    /// platform could not be identified.
+    pub async fn env_inconsistencies(&self) -> Result<Vec<Inconsistency>, DetectError> {
+        let resource = self.detect().await?;
+        Ok(resource.inconsistencies(consistency::env_attributes(&self.getter)))
+    }
+
+    /// Fetches an access token for the instance's default service account.
+    ///
+    /// Tokens are not cached; the metadata server already caches them and
+    /// refreshes them shortly before they expire.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`metadata::Error`] if the metadata server is unreachable, no
+    /// service account is attached, or the response is malformed.
+    #[cfg(feature = "token")]
+    pub async fn access_token(&self) -> Result<AccessToken, metadata::Error> {
+        let body = MetadataClient::resolve(&self.getter.metadata_client, AccessToken::PATH).await?;
+        AccessToken::parse(&body)
+    }
+
+    /// Runs resource detection inside a `gcp_resource_detection` span.
+    async fn run_detection(&self) -> Result<DetectedResource, DetectError> {
+        let span = tracing::info_span!(
+            "gcp_resource_detection",
+            resource_type = field::Empty,
+            probes = field::Empty,
+            probe_time_ms = field::Empty,
+            duration_ms = field::Empty,
+            outcome = field::Empty,
+        );
+        let getter = self.getter.session();
+        let start = Instant::now();
+        let (result, source) = self
+            .detect_from_sources(&getter)
+            .instrument(span.clone())
+            .await;
+        let elapsed = start.elapsed();
+        let diagnostics = record_detection(&span, &getter, elapsed, source);
+        *self
+            .diagnostics
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(diagnostics);
+        if self.log_summary {
+            log_summary(&result, elapsed);
+        }
+        match &result {
+            Ok(resource) => {
+                span.record("resource_type", resource.resource_type());
+                let outcome = match source {
+                    DetectionSource::Cache => "cached",
+                    DetectionSource::File => "file",
+                    _ => "detected",
+                };
+                span.record("outcome", outcome);
+            }
+            Err(err) => {
+                span.record("outcome", field::display(err));
+            }
+        }
+        result
+    }
+
+    /// Detects the resource from the resource file, the cache file, or the
+    /// metadata server, in the configured order.
+    async fn detect_from_sources(
+        &self,
+        getter: &ResourceAttributesGetter<Arc<dyn DynMetadataClient>>,
+    ) -> (Result<DetectedResource, DetectError>, DetectionSource) {
+        #[cfg(feature = "file-source")]
+        if let Some(file) = &self.resource_file
+            && file.priority == FileSourcePriority::BeforeMetadataServer
+            && let Some(resource) = file.load()
+        {
+            return (Ok(from_file(resource, getter)), DetectionSource::File);
+        }
+        let detected = self.detect_with_cache(getter).await;
+        #[cfg(feature = "file-source")]
+        if let Some(file) = &self.resource_file
+            && file.priority == FileSourcePriority::AfterMetadataServer
+            && detected.0.is_err()
+            && let Some(resource) = file.load()
+        {
+            return (Ok(from_file(resource, getter)), DetectionSource::File);
+        }
+        detected
+    }
+
+    /// Detects the resource, going through the cache file if one is
+    /// configured.
+    async fn detect_with_cache(
+        &self,
+        getter: &ResourceAttributesGetter<Arc<dyn DynMetadataClient>>,
+    ) -> (Result<DetectedResource, DetectError>, DetectionSource) {
+        #[cfg(feature = "file-cache")]
+        if let Some(cache) = &self.cache {
+            let key = CacheKey::current(getter).await;
+            if let Some(resource) = key.as_ref().and_then(|key| cache.load(key)) {
+                return (Ok(resource), DetectionSource::Cache);
+            }
+            let result = detect_resource(getter).await;
+            if let (Ok(resource), Some(key)) = (&result, key) {
+                cache.store(key, resource);
+            }
+            return (result, DetectionSource::MetadataServer);
+        }
+        (
+            detect_resource(getter).await,
+            DetectionSource::MetadataServer,
+        )
+    }
+
+    /// Runs attribute detection inside a `gcp_resource_attributes_detection` span.
+    async fn run_attributes_detection(&self) -> Option<GcpResourceAttributes> {
+        let span = tracing::info_span!(
+            "gcp_resource_attributes_detection",
+            cloud_platform = field::Empty,
+            probes = field::Empty,
+            probe_time_ms = field::Empty,
+            duration_ms = field::Empty,
+            outcome = field::Empty,
+        );
+        let getter = self.getter.session();
+        let start = Instant::now();
+        let attrs = detect_resource_attributes(&getter)
+            .instrument(span.clone())
+            .await;
+        let diagnostics = record_detection(
+            &span,
+            &getter,
+            start.elapsed(),
+            DetectionSource::MetadataServer,
+        );
+        let _ = self.attributes_diagnostics.set(diagnostics);
+        match &attrs {
+            Some(attrs) => {
+                if let Some(platform) = &attrs.cloud_platform {
+                    span.record("cloud_platform", platform.as_str());
+                }
+                span.record("outcome", "detected");
+            }
+            None => {
+                span.record("outcome", "unavailable");
+            }
+        }
+        attrs
+    }
+
+    #[cfg(feature = "integrations")]
+    async fn resource_cell(&self) -> Result<&Arc<MonitoredResource>, DetectError> {
+        let detected = self.detect().await?;
+        Ok(self.monitored_resource(detected))
+    }
+
+    /// Converts `detected` into the cached [`MonitoredResource`] on first use.
+    #[cfg(feature = "integrations")]
+    fn monitored_resource(&self, detected: &DetectedResource) -> &Arc<MonitoredResource> {
+        self.resource.get_or_init(|| Arc::new(detected.into()))
+    }
+}
+
+impl Default for Detector {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+impl fmt::Debug for Detector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Detector")
+            .field("scope", &self.getter.scope)
+            .field("detected", &self.detected.get())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Logs the one-line startup summary enabled by [`DetectorBuilder::log_summary`].
+fn log_summary(result: &Result<DetectedResource, DetectError>, elapsed: Duration) {
+    let duration_ms = millis(elapsed);
+    match result {
+        Ok(resource) => tracing::info!(
+            resource_type = resource.resource_type(),
+            project_id = resource.project_id(),
+            duration_ms,
+            "detected {resource} in {duration_ms}ms"
+        ),
+        Err(err) => tracing::warn!(
+            error = %err,
+            duration_ms,
+            "GCP resource detection failed after {duration_ms}ms: {err}"
+        ),
+    }
+}
+
+/// Applies the detector's scope to a resource read from a resource file.
+#[cfg(feature = "file-source")]
+fn from_file<C>(
+    mut resource: DetectedResource,
+    getter: &ResourceAttributesGetter<C>,
+) -> DetectedResource {
+    getter.scope.apply(&mut resource);
+    resource
+}
+
+/// Records the probe statistics of a detection run on its span.
+fn record_detection<C>(
+    span: &Span,
+    getter: &ResourceAttributesGetter<C>,
+    elapsed: Duration,
+    source: DetectionSource,
+) -> Diagnostics {
+    let diagnostics = Diagnostics {
+        total: elapsed,
+        probes: getter.probes(),
+        source,
+    };
+    span.record("probes", diagnostics.probes.len());
+    span.record("probe_time_ms", millis(diagnostics.probe_time()));
+    span.record("duration_ms", millis(elapsed));
+    diagnostics
+}
+
+fn millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+/// Builder for a [`Detector`].
+#[derive(Clone)]
+pub struct DetectorBuilder {
+    connect_timeout: Duration,
+    request_timeout: Duration,
+    metadata_host: Option<String>,
+    env: Option<Arc<HashMap<String, String>>>,
+    scope: Scope,
+    metadata_client: Option<Arc<dyn DynMetadataClient>>,
+    log_summary: bool,
+    sequential_debug: bool,
+    #[cfg(feature = "file-cache")]
+    cache_file: Option<PathBuf>,
+    #[cfg(feature = "file-source")]
+    resource_file: Option<(PathBuf, FileSourcePriority)>,
+}
+
+impl DetectorBuilder {
+    /// Sets the timeout for connecting to the metadata server. Defaults to 2 seconds.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Sets the timeout for a single metadata request. Defaults to 5 seconds.
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// Sets the metadata server `host[:port]`.
+    ///
+    /// Defaults to the `GCE_METADATA_HOST` environment variable, then to
+    /// `169.254.169.254`.
+    pub fn metadata_host(mut self, host: impl Into<String>) -> Self {
+        self.metadata_host = Some(host.into());
+        self
+    }
+
+    /// Uses `client` to query the metadata server instead of the built-in
+    /// HTTP client.
+    ///
+    /// The connect timeout, request timeout and metadata host settings only
+    /// apply to the built-in client and are ignored when this is set.
+    pub fn metadata_client(mut self, client: Arc<dyn DynMetadataClient>) -> Self {
+        self.metadata_client = Some(client);
+        self
+    }
+
+    /// Uses a fixed set of environment variables instead of the process
+    /// environment.
+    ///
+    /// Variables such as `K_SERVICE` or `GAE_SERVICE` decide which platform is
+    /// detected, so this lets a detector describe an environment other than
+    /// the one the process runs in.
+    pub fn env<K, V>(mut self, vars: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let vars = vars
+            .into_iter()
+            .map(|(key, value)| (key.into(), value.into()))
+            .collect();
+        self.env = Some(Arc::new(vars));
+        self
+    }
+
+    /// Reports `project_id` instead of the metadata server's project ID.
+    ///
+    /// See [`Detector::scoped`] for deriving tenant-scoped detectors from an
+    /// existing one.
+    pub fn project_id(mut self, project_id: impl Into<String>) -> Self {
+        self.scope.project_id = Some(project_id.into());
+        self
+    }
+
+    /// Reports `location` instead of the zone, region or location the
+    /// metadata server reports.
+    ///
+    /// See [`Detector::scoped`] for how the location maps onto zone- and
+    /// region-level labels.
+    pub fn location(mut self, location: impl Into<String>) -> Self {
+        self.scope.location = Some(location.into());
+        self
+    }
+
+    /// Logs a single `INFO` line once detection completes, e.g.
+    /// `detected cloud_run_revision service=x revision=y location=us-east1 project=z in 84ms`.
+    ///
+    /// Failed detections are logged at `WARN`. Disabled by default.
+    pub fn log_summary(mut self, enabled: bool) -> Self {
+        self.log_summary = enabled;
+        self
+    }
+
+    /// Runs metadata probes strictly one after another, in a fixed order, and
+    /// logs every probe and every platform check at `INFO`, e.g.
+    /// `Cloud Run service: false (K_CONFIGURATION set: false, FUNCTION_TARGET set: false)`.
+    ///
+    /// Detection is slower in this mode, but deterministic, which helps to
+    /// bisect misdetections in unusual environments. Disabled by default.
+    pub fn sequential_debug(mut self, enabled: bool) -> Self {
+        self.sequential_debug = enabled;
+        self
+    }
+
+    /// Persists the detected resource to `path` and reuses it on later runs.
+    ///
+    /// On startup the cached result is validated with a single request for
+    /// the instance ID, so a frequently restarting worker skips the remaining
+    /// metadata round trips. On Linux the result is also tied to the kernel's
+    /// boot ID, so a cache on a persistent disk is invalidated when the VM
+    /// restarts. The environment variables and overrides detection reads are
+    /// part of the key as well, so workloads sharing a machine, such as pods
+    /// on a GKE node, do not pick up each other's resource. The file is
+    /// rewritten whenever any of these change; read and write failures fall
+    /// back to regular detection.
+    #[cfg(feature = "file-cache")]
+    pub fn cache_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.cache_file = Some(path.into());
+        self
+    }
+
+    /// Reads a pre-computed [`DetectedResource`] from the JSON file at `path`,
+    /// e.g. one written by an init container or mounted as a systemd
+    /// credential.
+    ///
+    /// With [`FileSourcePriority::BeforeMetadataServer`] the metadata server
+    /// is only queried if the file does not exist or cannot be parsed; with
+    /// [`FileSourcePriority::AfterMetadataServer`] the file is only read if
+    /// detection fails. A configured project ID or location still applies.
+    ///
+    /// The file only feeds [`Detector::detect`] and the methods built on it.
+    /// [`Detector::resource_attributes`] always queries the metadata server
+    /// and returns `None` where it is unreachable, even if the file exists;
+    /// use [`DetectedResource::attributes`] for the file resource instead.
+    ///
+    /// ```no_run
+    /// use gcp_metadata_resolver::{Detector, FileSourcePriority};
+    ///
+    /// let detector = Detector::builder()
+    ///     .resource_file("/run/credentials/gcp-resource.json", FileSourcePriority::BeforeMetadataServer)
+    ///     .build();
+    /// ```
+    #[cfg(feature = "file-source")]
+    pub fn resource_file(mut self, path: impl Into<PathBuf>, priority: FileSourcePriority) -> Self {
+        self.resource_file = Some((path.into(), priority));
+        self
+    }
+
+    /// Builds the detector.
+    pub fn build(self) -> Detector {
+        let metadata_client = match self.metadata_client {
+            Some(client) => client,
+            None => {
+                let mut client = HttpMetadataClient::builder()
+                    .connect_timeout(self.connect_timeout)
+                    .request_timeout(self.request_timeout);
+                if let Some(host) = self.metadata_host {
+                    client = client.host(host);
+                }
+                Arc::new(client.build())
+            }
+        };
+        let mut getter = match self.env {
+            Some(vars) => ResourceAttributesGetter::new(metadata_client, move |key| {
+                vars.get(key).cloned().ok_or(VarError::NotPresent)
+            }),
+            None => ResourceAttributesGetter::new(metadata_client, |key| env::var(key)),
+        };
+        getter.scope = self.scope;
+        getter.debug = self.sequential_debug;
+        let detector = Detector::from_getter(getter, self.log_summary);
+        #[cfg(feature = "file-cache")]
+        let detector = Detector {
+            cache: self.cache_file.map(FileCache::new),
+            ..detector
+        };
+        #[cfg(feature = "file-source")]
+        let detector = Detector {
+            resource_file: self
+                .resource_file
+                .map(|(path, priority)| ResourceFile::new(path, priority)),
+            ..detector
+        };
+        detector
+    }
+}
+
+impl Default for DetectorBuilder {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(2),
+            request_timeout: Duration::from_secs(5),
+            metadata_host: None,
+            env: None,
+            scope: Scope::default(),
+            metadata_client: None,
+            log_summary: false,
+            sequential_debug: false,
+            #[cfg(feature = "file-cache")]
+            cache_file: None,
+            #[cfg(feature = "file-source")]
+            resource_file: None,
+        }
+    }
+}
+
+impl fmt::Debug for DetectorBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut builder = f.debug_struct("DetectorBuilder");
+        builder
+            .field("connect_timeout", &self.connect_timeout)
+            .field("request_timeout", &self.request_timeout)
+            .field("metadata_host", &self.metadata_host)
+            .field("env", &self.env)
+            .field("scope", &self.scope)
+            .field("custom_metadata_client", &self.metadata_client.is_some())
+            .field("log_summary", &self.log_summary)
+            .field("sequential_debug", &self.sequential_debug);
+        #[cfg(feature = "file-cache")]
+        builder.field("cache_file", &self.cache_file);
+        #[cfg(feature = "file-source")]
+        builder.field("resource_file", &self.resource_file);
+        builder.finish()
+    }
+}
+
+/// Installs `detector` as the global detector used by the free functions of
+/// this crate.
+///
+/// This should be called early, typically from `main`, so that libraries
+/// deeper in the stack pick up the application's configuration.
+///
+/// # Errors
+///
+/// Returns [`SetGlobalDetectorError`] if a global detector was already
+/// installed, or if the default one was already created by a previous call to
+/// any of the free functions.
+pub fn set_global_detector(detector: Detector) -> Result<(), SetGlobalDetectorError> {
+    GLOBAL_DETECTOR
+        .set(detector)
+        .map_err(|_| SetGlobalDetectorError(()))
+}
+
+/// Returned by [`set_global_detector`] when the global detector is already in place.
+#[derive(Debug, Error)]
+#[error("a global detector has already been set")]
+pub struct SetGlobalDetectorError(());
+
+/// Returns the global detector, creating a default one if none was installed.
+pub(crate) fn global() -> &'static Detector {
+    GLOBAL_DETECTOR.get_or_init(Detector::default)
+}
+
+/// Returns the global detector if it has been created or installed, for reads
+/// that must neither block nor create it.
+pub(crate) fn try_global() -> Option<&'static Detector> {
+    GLOBAL_DETECTOR.get()
+}
+
+static GLOBAL_DETECTOR: OnceLock<Detector> = OnceLock::new();
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ProbeOutcome;
+    use crate::metadata::{self, MetadataClient};
+    use crate::test_util::FakeMetadataClient;
+    use crate::test_util::server::TestServer;
+
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    const CLOUD_RUN: &[(&str, &str)] = &[
+        ("", "ok"),
+        ("instance/id", "1234567891"),
+        ("instance/region", "projects/123/regions/us-east1"),
+    ];
+
+    fn cloud_run_detector(addr: SocketAddr) -> Detector {
+        Detector::builder()
+            .metadata_host(addr.to_string())
+            .env([
+                ("K_CONFIGURATION", "my-config"),
+                ("K_SERVICE", "my-service"),
+            ])
+            .build()
+    }
+
+    #[tokio::test]
+    async fn independent_detectors() {
+        let server_a =
+            TestServer::with_values(&[CLOUD_RUN, &[("project/project-id", "project-a")]].concat())
+                .await;
+        let server_b =
+            TestServer::with_values(&[CLOUD_RUN, &[("project/project-id", "project-b")]].concat())
+                .await;
+        let a = cloud_run_detector(server_a.addr());
+        let b = cloud_run_detector(server_b.addr());
+
+        assert_eq!(a.detect().await.unwrap().project_id(), "project-a");
+        assert_eq!(b.detect().await.unwrap().project_id(), "project-b");
+        assert!(matches!(
+            a.detect().await.unwrap(),
+            DetectedResource::CloudRunRevision { service_name, location, .. }
+                if service_name.as_deref() == Some("my-service")
+                    && location.as_deref() == Some("us-east1")
+        ));
+    }
+
+    #[tokio::test]
+    async fn try_detect_after_detect() {
+        let server =
+            TestServer::with_values(&[CLOUD_RUN, &[("project/project-id", "my-project")]].concat())
+                .await;
+        let detector = cloud_run_detector(server.addr());
+        assert!(detector.try_detect().is_none());
+
+        let detected = detector.detect().await.unwrap();
+        assert_eq!(detector.try_detect(), Some(detected));
+        #[cfg(feature = "integrations")]
+        assert!(matches!(
+            detector.try_detected_resource(),
+            Some(MonitoredResource::CloudRunRevision { .. })
+        ));
+    }
+
+    /// A zonal GKE cluster, served by a [`FakeMetadataClient`].
+    fn zonal_gke_detector(env: &[(&str, &str)]) -> Detector {
+        let client = FakeMetadataClient::new([
+            ("", "ok"),
+            ("project/project-id", "my-project"),
+            ("instance/id", "1234567891"),
+            ("instance/zone", "projects/123/zones/us-central1-a"),
+            ("instance/attributes/cluster-name", "my-cluster"),
+            ("instance/attributes/cluster-location", "us-central1-a"),
+        ]);
+        Detector::builder()
+            .metadata_client(Arc::new(client))
+            .env(env.iter().copied())
+            .build()
+    }
+
+    #[tokio::test]
+    async fn resource_agrees_with_attributes() {
+        let detector = zonal_gke_detector(&[]);
+        let resource = detector.detect().await.unwrap();
+        let attributes = detector.resource_attributes().await.unwrap();
+        assert_eq!(resource.inconsistencies(attributes.iter()), []);
+        assert_eq!(
+            resource.inconsistencies([("cloud.region", "us-central1")]),
+            []
+        );
+    }
+
+    #[tokio::test]
+    async fn env_inconsistencies_use_detector_env() {
+        let detector = zonal_gke_detector(&[(
+            "OTEL_RESOURCE_ATTRIBUTES",
+            "cloud.region=us-central1,k8s.cluster.name=other-cluster",
+        )]);
+        let inconsistencies = detector.env_inconsistencies().await.unwrap();
+        assert_eq!(
+            inconsistencies
+                .iter()
+                .map(|inconsistency| inconsistency.key.as_str())
+                .collect::<Vec<_>>(),
+            ["k8s.cluster.name"]
+        );
+        assert_eq!(
+            zonal_gke_detector(&[]).env_inconsistencies().await.unwrap(),
+            []
+        );
+    }
+
+    #[cfg(feature = "integrations")]
+    #[tokio::test]
+    async fn detector_caches_result() {
+        let server =
+            TestServer::with_values(&[CLOUD_RUN, &[("project/project-id", "my-project")]].concat())
+                .await;
+        let detector = cloud_run_detector(server.addr());
+        assert!(detector.try_detected_resource().is_none());
+
+        let initialized = detector.init().await.unwrap();
+        assert!(initialized.performed_detection);
+        let served = server.requests();
+
+        let initialized = detector.init().await.unwrap();
+        assert!(!initialized.performed_detection);
+        detector.detected_resource().await.unwrap();
+        assert!(detector.try_detected_resource().is_some());
+        assert_eq!(server.requests(), served);
+
+        let diagnostics = detector.diagnostics().unwrap();
+        assert_eq!(diagnostics.probes.len(), served);
+        assert!(diagnostics.probe_time() >= diagnostics.slowest_probe().unwrap().duration);
+        assert!(detector.attributes_diagnostics().is_none());
+    }
+
+    /// Serves [`CLOUD_RUN`] once `up` is set, and fails every request before.
+    struct FlakyMetadataClient {
+        up: AtomicBool,
+    }
+
+    impl MetadataClient for FlakyMetadataClient {
+        async fn resolve_etag(
+            &self,
+            suffix: &str,
+        ) -> Result<(String, Option<String>), metadata::Error> {
+            Ok((MetadataClient::resolve(self, suffix).await?, None))
+        }
+
+        async fn resolve(&self, suffix: &str) -> Result<String, metadata::Error> {
+            if !self.up.load(Ordering::SeqCst) {
+                return Err(metadata::Error::RequestTimeout);
+            }
+            CLOUD_RUN
+                .iter()
+                .chain(&[("project/project-id", "my-project")])
+                .find(|(key, _)| *key == suffix)
+                .map(|(_, value)| value.to_string())
+                .ok_or_else(|| metadata::Error::NotDefined(suffix.to_owned()))
+        }
+    }
+
+    #[tokio::test]
+    async fn diagnostics_report_latest_run() {
+        let client = Arc::new(FlakyMetadataClient {
+            up: AtomicBool::new(false),
+        });
+        let detector = Detector::builder()
+            .metadata_client(client.clone())
+            .env([
+                ("K_CONFIGURATION", "my-config"),
+                ("K_SERVICE", "my-service"),
+            ])
+            .build();
+
+        assert!(detector.detect().await.is_err());
+        let failed = detector.diagnostics().unwrap();
+        assert!(
+            failed
+                .probes
+                .iter()
+                .all(|probe| probe.outcome != ProbeOutcome::Found)
+        );
+
+        client.up.store(true, Ordering::SeqCst);
+        detector.detect().await.unwrap();
+        let succeeded = detector.diagnostics().unwrap();
+        assert!(
+            succeeded
+                .probes
+                .iter()
+                .any(|probe| probe.outcome == ProbeOutcome::Found)
+        );
+    }
+
+    #[cfg(feature = "file-cache")]
+    #[tokio::test]
+    async fn cache_file_skips_detection() {
+        let path = env::temp_dir().join(format!("gcp-resource-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let server =
+            TestServer::with_values(&[CLOUD_RUN, &[("project/project-id", "my-project")]].concat())
+                .await;
+        let detected = Detector::builder()
+            .metadata_host(server.addr().to_string())
+            .env([
+                ("K_CONFIGURATION", "my-config"),
+                ("K_SERVICE", "my-service"),
+            ])
+            .cache_file(&path)
+            .build()
+            .detect()
+            .await
+            .unwrap()
+            .clone();
+
+        // Only the instance ID is needed to validate the cached result.
+        let server = TestServer::with_values(&[("instance/id", "1234567891")]).await;
+        let detector = Detector::builder()
+            .metadata_host(server.addr().to_string())
+            .env([
+                ("K_CONFIGURATION", "my-config"),
+                ("K_SERVICE", "my-service"),
+            ])
+            .cache_file(&path)
+            .build();
+        assert_eq!(detector.detect().await.unwrap(), &detected);
+        assert_eq!(server.requests(), 1);
+        assert_eq!(
+            detector.diagnostics().unwrap().source,
+            DetectionSource::Cache
+        );
+
+        // A different environment on the same instance invalidates the cache.
+        let server =
+            TestServer::with_values(&[CLOUD_RUN, &[("project/project-id", "my-project")]].concat())
+                .await;
+        let detector = Detector::builder()
+            .metadata_host(server.addr().to_string())
+            .env([
+                ("K_CONFIGURATION", "my-config"),
+                ("K_SERVICE", "other-service"),
+            ])
+            .cache_file(&path)
+            .build();
+        assert!(matches!(
+            detector.detect().await.unwrap(),
+            DetectedResource::CloudRunRevision { service_name, .. }
+                if service_name.as_deref() == Some("other-service")
+        ));
+        assert_eq!(
+            detector.diagnostics().unwrap().source,
+            DetectionSource::MetadataServer
+        );
+
+        // A different instance invalidates the cache.
+        let server = TestServer::with_values(
+            &[
+                CLOUD_RUN,
+                &[
+                    ("instance/id", "42"),
+                    ("project/project-id", "other-project"),
+                ],
+            ]
+            .concat(),
+        )
+        .await;
+        let detector = Detector::builder()
+            .metadata_host(server.addr().to_string())
+            .env([
+                ("K_CONFIGURATION", "my-config"),
+                ("K_SERVICE", "my-service"),
+            ])
+            .cache_file(&path)
+            .build();
+        assert_eq!(
+            detector.detect().await.unwrap().project_id(),
+            "other-project"
+        );
+        assert_eq!(
+            detector.diagnostics().unwrap().source,
+            DetectionSource::MetadataServer
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(feature = "file-source")]
+    #[tokio::test]
+    async fn resource_file_priority() {
+        let path = env::temp_dir().join(format!("gcp-resource-file-{}.json", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"{"type": "cloud_run_job", "project_id": "file-project", "job_name": "my-job"}"#,
+        )
+        .unwrap();
+        let from_file = DetectedResource::CloudRunJob {
+            project_id: "file-project".to_owned(),
+            job_name: Some("my-job".to_owned()),
+            location: None,
+        };
+
+        let server =
+            TestServer::with_values(&[CLOUD_RUN, &[("project/project-id", "my-project")]].concat())
+                .await;
+        let before = Detector::builder()
+            .metadata_host(server.addr().to_string())
+            .resource_file(&path, FileSourcePriority::BeforeMetadataServer)
+            .build();
+        assert_eq!(before.detect().await.unwrap(), &from_file);
+        assert_eq!(before.diagnostics().unwrap().source, DetectionSource::File);
+        assert_eq!(server.requests(), 0);
+        let scoped = before.scoped("tenant", "europe-west1");
+        assert_eq!(scoped.detect().await.unwrap().project_id(), "tenant");
+
+        let after = Detector::builder()
+            .metadata_host(server.addr().to_string())
+            .env([
+                ("K_CONFIGURATION", "my-config"),
+                ("K_SERVICE", "my-service"),
+            ])
+            .resource_file(&path, FileSourcePriority::AfterMetadataServer)
+            .build();
+        assert_eq!(after.detect().await.unwrap().project_id(), "my-project");
+
+        // Detection fails without a project ID, so the file is used.
+        let server = TestServer::with_values(&[("", "ok")]).await;
+        let fallback = Detector::builder()
+            .metadata_host(server.addr().to_string())
+            .resource_file(&path, FileSourcePriority::AfterMetadataServer)
+            .build();
+        assert_eq!(fallback.detect().await.unwrap(), &from_file);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn emulator_host_and_directory_listings() {
+        let server = TestServer::with_values(
+            &[
+                CLOUD_RUN,
+                &[
+                    ("project/project-id", "my-project"),
+                    ("instance/", "id\nregion\n"),
+                ],
+            ]
+            .concat(),
+        )
+        .await;
+        let detector = Detector::builder().metadata_host(server.url()).build();
+        assert_eq!(detector.project_id().await.as_deref(), Some("my-project"));
+        let listing = MetadataClient::resolve(&detector.getter.metadata_client, "instance/").await;
+        assert_eq!(listing.unwrap(), "id\nregion\n");
+    }
+
+    #[cfg(feature = "token")]
+    #[tokio::test]
+    async fn access_token() {
+        let server = TestServer::with_values(&[(
+            AccessToken::PATH,
+            r#"{"access_token":"ya29.secret","expires_in":3599,"token_type":"Bearer"}"#,
+        )])
+        .await;
+        let detector = Detector::builder()
+            .metadata_host(server.addr().to_string())
+            .build();
+        let token = detector.access_token().await.unwrap();
+        assert_eq!(token.access_token, "ya29.secret");
+        assert_eq!(token.token_type, "Bearer");
+        assert_eq!(token.expires_in, Duration::from_secs(3599));
+        assert!(!format!("{token:?}").contains("ya29"));
+    }
+
+    #[tokio::test]
+    async fn scoped_detectors() {
+        let server = TestServer::with_values(
+            &[CLOUD_RUN, &[("project/project-id", "host-project")]].concat(),
+        )
+        .await;
+        let detector = cloud_run_detector(server.addr());
+        let tenant_a = detector.scoped("tenant-a", "europe-west1");
+        let tenant_b = detector.scoped("tenant-b", "asia-east1");
+
+        assert_eq!(
+            detector.detect().await.unwrap().project_id(),
+            "host-project"
+        );
+        let a = tenant_a.detect().await.unwrap();
+        assert!(matches!(
+            a,
+            DetectedResource::CloudRunRevision { project_id, service_name, location, .. }
+                if project_id == "tenant-a"
+                    && service_name.as_deref() == Some("my-service")
+                    && location.as_deref() == Some("europe-west1")
+        ));
+        assert_eq!(tenant_b.detect().await.unwrap().project_id(), "tenant-b");
+    }
+
+    #[test]
+    fn set_global_detector_once() {
+        assert!(set_global_detector(Detector::default()).is_ok());
+        assert!(set_global_detector(Detector::default()).is_err());
+    }
+}
@@ -0,0 +1,80 @@
+//! Timing information about a detection run.
+use std::time::Duration;
+
+use crate::metadata;
+
+/// Timings of a single detection run, returned by [`Detector::diagnostics`](crate::Detector::diagnostics).
+///
+/// Probes may run concurrently, so the sum of their durations can exceed
+/// [`total`](Self::total).
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Diagnostics {
+    /// Wall-clock duration of the whole detection.
+    pub total: Duration,
+    /// Every metadata request made during detection, in completion order.
+    pub probes: Vec<ProbeTiming>,
+    /// Where the detected resource came from.
+    pub source: DetectionSource,
+}
+
+impl Diagnostics {
+    /// Returns the summed duration of all probes.
+    pub fn probe_time(&self) -> Duration {
+        self.probes.iter().map(|probe| probe.duration).sum()
+    }
+
+    /// Returns the slowest probe, if any.
+    pub fn slowest_probe(&self) -> Option<&ProbeTiming> {
+        self.probes.iter().max_by_key(|probe| probe.duration)
+    }
+}
+
+/// Where the result of a detection run came from, see [`Diagnostics::source`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DetectionSource {
+    /// Detected by querying the metadata server.
+    MetadataServer,
+    /// Loaded from the cache file, see `DetectorBuilder::cache_file`.
+    Cache,
+    /// Read from the resource file, see `DetectorBuilder::resource_file`.
+    File,
+}
+
+/// A single metadata request made during detection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ProbeTiming {
+    /// The metadata path, relative to `computeMetadata/v1/`.
+    pub path: String,
+    /// How long the request took.
+    pub duration: Duration,
+    /// How the request ended.
+    pub outcome: ProbeOutcome,
+}
+
+/// How a [`ProbeTiming`] ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ProbeOutcome {
+    /// The metadata server returned a value.
+    Found,
+    /// The path is not defined on this platform.
+    NotDefined,
+    /// The request hit the request timeout.
+    TimedOut,
+    /// Any other error, e.g. the metadata server is unreachable.
+    Failed,
+}
+
+impl ProbeOutcome {
+    pub(crate) fn of<T>(result: &Result<T, metadata::Error>) -> Self {
+        match result {
+            Ok(_) => Self::Found,
+            Err(metadata::Error::NotDefined(_)) => Self::NotDefined,
+            Err(metadata::Error::RequestTimeout) => Self::TimedOut,
+            Err(_) => Self::Failed,
+        }
+    }
+}
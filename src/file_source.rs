@@ -0,0 +1,51 @@
+//! A detection source reading a pre-computed resource from a file, see
+//! [`DetectorBuilder::resource_file`](crate::DetectorBuilder::resource_file).
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::DetectedResource;
+
+/// When a [resource file](crate::DetectorBuilder::resource_file) is consulted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FileSourcePriority {
+    /// Use the file whenever it exists and only query the metadata server
+    /// without it.
+    BeforeMetadataServer,
+    /// Only read the file if detection via the metadata server fails.
+    AfterMetadataServer,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct ResourceFile {
+    path: PathBuf,
+    pub(crate) priority: FileSourcePriority,
+}
+
+impl ResourceFile {
+    pub(crate) fn new(path: PathBuf, priority: FileSourcePriority) -> Self {
+        Self { path, priority }
+    }
+
+    /// Reads the resource, or returns `None` if the file does not exist or
+    /// is invalid.
+    pub(crate) fn load(&self) -> Option<DetectedResource> {
+        let contents = match fs::read(&self.path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                tracing::debug!(path = %self.path.display(), "Resource file does not exist");
+                return None;
+            }
+            Err(err) => {
+                tracing::warn!(?err, path = %self.path.display(), "Failed to read resource file");
+                return None;
+            }
+        };
+        serde_json::from_slice(&contents)
+            .inspect_err(|err| {
+                tracing::warn!(?err, path = %self.path.display(), "Failed to parse resource file");
+            })
+            .ok()
+    }
+}
@@ -0,0 +1,240 @@
+//! Typed GCP [zones and regions](https://cloud.google.com/compute/docs/regions-zones).
+//!
+//! Detection reports locations as plain strings, matching the metadata server
+//! and the resource labels. [`Zone`] and [`Region`] validate such strings and
+//! derive the region of a zone the same way detection does, so telemetry from
+//! other sources can be labelled consistently.
+use std::fmt;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+/// A GCP zone, e.g. `us-central1-a`.
+///
+/// A zone is a [`Region`] followed by a dash and a zone suffix. All parts are
+/// lower-case ASCII letters and digits.
+///
+/// ```
+/// use gcp_metadata_resolver::Zone;
+///
+/// let zone: Zone = "us-central1-a".parse().unwrap();
+/// assert_eq!(zone.region().as_str(), "us-central1");
+/// assert!("us-central1".parse::<Zone>().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "String", into = "String")
+)]
+pub struct Zone(String);
+
+/// A GCP region, e.g. `us-central1`.
+///
+/// A region consists of two dash-separated parts of lower-case ASCII letters
+/// and digits.
+///
+/// ```
+/// use gcp_metadata_resolver::Region;
+///
+/// let region: Region = "europe-west1".parse().unwrap();
+/// assert_eq!(region.to_string(), "europe-west1");
+/// assert!("europe-west1-b".parse::<Region>().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "String", into = "String")
+)]
+pub struct Region(String);
+
+/// The error returned when parsing an invalid [`Zone`] or [`Region`].
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("invalid {kind}: {input:?}")]
+pub struct ParseLocationError {
+    kind: &'static str,
+    input: String,
+}
+
+impl Zone {
+    /// Parses a zone, e.g. `us-central1-a`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ParseLocationError`] if `zone` is not a valid zone.
+    pub fn new(zone: impl Into<String>) -> Result<Self, ParseLocationError> {
+        let zone = zone.into();
+        if has_parts(&zone, 3) {
+            Ok(Self(zone))
+        } else {
+            Err(ParseLocationError {
+                kind: "zone",
+                input: zone,
+            })
+        }
+    }
+
+    /// Returns the region the zone belongs to.
+    pub fn region(&self) -> Region {
+        let (region, _) = self.0.rsplit_once('-').expect("validated zone");
+        Region(region.to_owned())
+    }
+
+    /// Returns the zone as a string slice.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Region {
+    /// Parses a region, e.g. `us-central1`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ParseLocationError`] if `region` is not a valid region.
+    pub fn new(region: impl Into<String>) -> Result<Self, ParseLocationError> {
+        let region = region.into();
+        if has_parts(&region, 2) {
+            Ok(Self(region))
+        } else {
+            Err(ParseLocationError {
+                kind: "region",
+                input: region,
+            })
+        }
+    }
+
+    /// Returns the region as a string slice.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Returns whether `location` consists of exactly `parts` dash-separated,
+/// non-empty parts of lower-case ASCII letters and digits.
+fn has_parts(location: &str, parts: usize) -> bool {
+    location.split('-').count() == parts
+        && location.split('-').all(|part| {
+            !part.is_empty()
+                && part
+                    .bytes()
+                    .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit())
+        })
+}
+
+/// Returns the region of a zone, the part before its last dash.
+///
+/// Unlike [`Zone::region`], this accepts zones that [`Zone`] rejects, e.g.
+/// with upper-case letters, as the metadata server reports them verbatim.
+pub(crate) fn zone_region(zone: &str) -> Option<&str> {
+    zone.rsplit_once('-').map(|(region, _)| region)
+}
+
+/// Splits a zonal (`us-central1-a`) or regional (`us-central1`) location into
+/// `(region, zone)`. Locations that are neither are reported as the region.
+///
+/// Every location with two dashes counts as zonal, including those [`Zone`]
+/// rejects, so an unusual zone is not reported as a region.
+pub(crate) fn split_location(location: &str) -> (&str, Option<&str>) {
+    match zone_region(location) {
+        Some(region) if location.matches('-').count() == 2 => (region, Some(location)),
+        _ => (location, None),
+    }
+}
+
+macro_rules! impl_location_traits {
+    ($ty:ident) => {
+        impl FromStr for $ty {
+            type Err = ParseLocationError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                Self::new(s)
+            }
+        }
+
+        impl TryFrom<String> for $ty {
+            type Error = ParseLocationError;
+
+            fn try_from(s: String) -> Result<Self, Self::Error> {
+                Self::new(s)
+            }
+        }
+
+        impl From<$ty> for String {
+            fn from(location: $ty) -> Self {
+                location.0
+            }
+        }
+
+        impl AsRef<str> for $ty {
+            fn as_ref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl fmt::Display for $ty {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+    };
+}
+
+impl_location_traits!(Zone);
+impl_location_traits!(Region);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zone_region() {
+        let zone = Zone::new("europe-west10-b").unwrap();
+        assert_eq!(zone.region(), Region::new("europe-west10").unwrap());
+        assert_eq!(zone.as_str(), "europe-west10-b");
+    }
+
+    #[test]
+    fn invalid_locations() {
+        for zone in [
+            "",
+            "us-central1",
+            "us-central1-",
+            "US-central1-a",
+            "a-b-c-d",
+            "us-central1-a/",
+        ] {
+            assert!(Zone::new(zone).is_err(), "{zone:?}");
+        }
+        for region in ["", "us", "us-", "-us", "us-central1-a", "us_central1"] {
+            assert!(Region::new(region).is_err(), "{region:?}");
+        }
+        assert_eq!(
+            "us".parse::<Region>().unwrap_err().to_string(),
+            r#"invalid region: "us""#
+        );
+    }
+
+    #[test]
+    fn split_zonal_and_regional_locations() {
+        assert_eq!(
+            split_location("us-central1-c"),
+            ("us-central1", Some("us-central1-c"))
+        );
+        assert_eq!(split_location("us-central1"), ("us-central1", None));
+        // Not a valid `Zone`, but still zonal.
+        assert_eq!(
+            split_location("us-east1-B"),
+            ("us-east1", Some("us-east1-B"))
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_validates() {
+        let zone: Zone = serde_json::from_str(r#""us-east1-b""#).unwrap();
+        assert_eq!(serde_json::to_string(&zone).unwrap(), r#""us-east1-b""#);
+        assert!(serde_json::from_str::<Region>(r#""us-east1-b""#).is_err());
+    }
+}
@@ -1,34 +1,238 @@
 //! A small client for the Google Cloud Platform metadata service.
+//!
+//! [`MetadataClient`] is the statically dispatched client trait used by the
+//! detector. [`DynMetadataClient`] is its object-safe counterpart; every
+//! `MetadataClient` implements it, and `Arc<dyn DynMetadataClient>` implements
+//! `MetadataClient` again, so custom clients can be passed around as trait
+//! objects, e.g. to [`DetectorBuilder::metadata_client`](crate::DetectorBuilder::metadata_client).
+//!
+//! With the `client` feature, [`HttpMetadataClient`] is the client the
+//! detector uses by default. It can also be used on its own to query
+//! arbitrary metadata paths:
+//!
+//! ```no_run
+//! # #[cfg(feature = "client")]
+//! # async fn run() -> Result<(), gcp_metadata_resolver::metadata::Error> {
+//! use std::time::Duration;
+//!
+//! use gcp_metadata_resolver::metadata::{HttpMetadataClient, MetadataClient};
+//!
+//! let client = HttpMetadataClient::builder()
+//!     .request_timeout(Duration::from_secs(1))
+//!     .user_agent("my-agent/1.0")
+//!     .build();
+//! let hostname = client.resolve("instance/hostname").await?;
+//! # Ok(())
+//! # }
+//! ```
+use std::future::Future;
+use std::pin::Pin;
+#[cfg(feature = "client")]
 use std::str;
+use std::sync::Arc;
+#[cfg(any(feature = "client", feature = "token"))]
 use std::time::Duration;
 
+use http::StatusCode;
+#[cfg(feature = "client")]
 use http_body_util::{BodyExt, Full};
-use hyper::{StatusCode, body::Bytes};
-use hyper_util::client::legacy::{Client, connect::HttpConnector};
+#[cfg(feature = "client")]
+use hyper::body::Bytes;
+#[cfg(feature = "client")]
+use hyper_util::client::legacy::Client;
+#[cfg(feature = "client")]
+use hyper_util::client::legacy::connect::{Connect, HttpConnector};
+#[cfg(feature = "client")]
+use hyper_util::rt::TokioExecutor;
 use thiserror::Error;
+#[cfg(feature = "client")]
 use tokio::time::timeout;
 
 /// A client for the GCP metadata service.
-#[allow(async_fn_in_trait)]
+///
+/// Implementations can use `async fn` as long as the returned futures are `Send`.
 pub trait MetadataClient {
     /// Returns a value from the metadata service as well as the associated ETag.
-    async fn resolve_etag(&self, suffix: &str) -> Result<(String, Option<String>), Error>;
+    fn resolve_etag(
+        &self,
+        suffix: &str,
+    ) -> impl Future<Output = Result<(String, Option<String>), Error>> + Send;
+
+    /// Returns a value from the metadata service.
+    fn resolve(&self, suffix: &str) -> impl Future<Output = Result<String, Error>> + Send;
+}
+
+/// An owned, boxed future, as returned by [`DynMetadataClient`].
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// An object-safe version of [`MetadataClient`].
+///
+/// Implemented for every `MetadataClient` that is `Send + Sync`, so it rarely
+/// needs to be implemented by hand.
+pub trait DynMetadataClient: Send + Sync {
+    /// Returns a value from the metadata service as well as the associated ETag.
+    fn resolve_etag<'a>(
+        &'a self,
+        suffix: &'a str,
+    ) -> BoxFuture<'a, Result<(String, Option<String>), Error>>;
 
     /// Returns a value from the metadata service.
-    async fn resolve(&self, suffix: &str) -> Result<String, Error>;
+    fn resolve<'a>(&'a self, suffix: &'a str) -> BoxFuture<'a, Result<String, Error>>;
 }
 
-pub struct HttpMetadataClient {
-    client: Client<HttpConnector, Full<Bytes>>,
+impl<T: MetadataClient + Send + Sync> DynMetadataClient for T {
+    fn resolve_etag<'a>(
+        &'a self,
+        suffix: &'a str,
+    ) -> BoxFuture<'a, Result<(String, Option<String>), Error>> {
+        Box::pin(MetadataClient::resolve_etag(self, suffix))
+    }
+
+    fn resolve<'a>(&'a self, suffix: &'a str) -> BoxFuture<'a, Result<String, Error>> {
+        Box::pin(MetadataClient::resolve(self, suffix))
+    }
 }
 
+impl<'c> MetadataClient for Arc<dyn DynMetadataClient + 'c> {
+    async fn resolve_etag(&self, suffix: &str) -> Result<(String, Option<String>), Error> {
+        DynMetadataClient::resolve_etag(&**self, suffix).await
+    }
+
+    async fn resolve(&self, suffix: &str) -> Result<String, Error> {
+        DynMetadataClient::resolve(&**self, suffix).await
+    }
+}
+
+impl<'c> MetadataClient for Box<dyn DynMetadataClient + 'c> {
+    async fn resolve_etag(&self, suffix: &str) -> Result<(String, Option<String>), Error> {
+        DynMetadataClient::resolve_etag(&**self, suffix).await
+    }
+
+    async fn resolve(&self, suffix: &str) -> Result<String, Error> {
+        DynMetadataClient::resolve(&**self, suffix).await
+    }
+}
+
+/// A [`MetadataClient`] querying the metadata server over HTTP.
+///
+/// Created with [`HttpMetadataClient::builder`]. Cloning is cheap; clones
+/// share the connection pool.
+#[cfg(feature = "client")]
+#[derive(Debug, Clone)]
+pub struct HttpMetadataClient<C = HttpConnector> {
+    client: Client<C, Full<Bytes>>,
+    /// Overrides the metadata host. Falls back to `GCE_METADATA_HOST`.
+    host: Option<String>,
+    timeout: Duration,
+    user_agent: String,
+}
+
+#[cfg(feature = "client")]
 impl HttpMetadataClient {
-    pub fn new(client: Client<HttpConnector, Full<Bytes>>) -> Self {
-        Self { client }
+    /// Returns a builder for a new client.
+    pub fn builder() -> HttpMetadataClientBuilder {
+        HttpMetadataClientBuilder::default()
+    }
+}
+
+#[cfg(feature = "client")]
+impl Default for HttpMetadataClient {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+/// Builder for an [`HttpMetadataClient`].
+#[cfg(feature = "client")]
+#[derive(Debug, Clone)]
+pub struct HttpMetadataClientBuilder<C = HttpConnector> {
+    connector: C,
+    host: Option<String>,
+    request_timeout: Duration,
+    user_agent: String,
+}
+
+#[cfg(feature = "client")]
+impl HttpMetadataClientBuilder {
+    /// Sets the timeout for connecting to the metadata server. Defaults to 2 seconds.
+    ///
+    /// Only available with the default connector; custom connectors are
+    /// configured before being passed to [`connector`](Self::connector).
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connector.set_connect_timeout(Some(timeout));
+        self
+    }
+}
+
+#[cfg(feature = "client")]
+impl<C> HttpMetadataClientBuilder<C> {
+    /// Overrides the metadata host, given as `host:port` or as an `http://`
+    /// URL. Defaults to `GCE_METADATA_HOST`, or `169.254.169.254` if unset.
+    ///
+    /// Requests fail with [`Error::UnsupportedScheme`] for other URL schemes,
+    /// such as `https://`.
+    pub fn host(mut self, host: impl Into<String>) -> Self {
+        self.host = Some(host.into());
+        self
+    }
+
+    /// Sets the timeout for a single metadata request. Defaults to 5 seconds.
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// Sets the `User-Agent` header. Defaults to [`USER_AGENT`].
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// Uses `connector` to connect to the metadata server, e.g. to route
+    /// requests through a proxy or a custom DNS resolver.
+    pub fn connector<D>(self, connector: D) -> HttpMetadataClientBuilder<D> {
+        HttpMetadataClientBuilder {
+            connector,
+            host: self.host,
+            request_timeout: self.request_timeout,
+            user_agent: self.user_agent,
+        }
+    }
+
+    /// Builds the client.
+    pub fn build(self) -> HttpMetadataClient<C>
+    where
+        C: Connect + Clone,
+    {
+        // Set up a hyper client with the same timeouts as the go SDK.
+        let client = Client::builder(TokioExecutor::new())
+            .pool_idle_timeout(Duration::from_secs(60))
+            .build(self.connector);
+        HttpMetadataClient {
+            client,
+            host: self.host,
+            timeout: self.request_timeout,
+            user_agent: self.user_agent,
+        }
     }
 }
 
-impl MetadataClient for HttpMetadataClient {
+#[cfg(feature = "client")]
+impl Default for HttpMetadataClientBuilder {
+    fn default() -> Self {
+        let mut connector = HttpConnector::new();
+        connector.set_connect_timeout(Some(Duration::from_secs(2)));
+        Self {
+            connector,
+            host: None,
+            request_timeout: Duration::from_secs(5),
+            user_agent: USER_AGENT.to_owned(),
+        }
+    }
+}
+
+#[cfg(feature = "client")]
+impl<C: Connect + Clone + Send + Sync + 'static> MetadataClient for HttpMetadataClient<C> {
     /// Returns a value from the metadata service as well as the associated ETag.
     ///
     /// Follows the go SDK implementation.
@@ -38,23 +242,31 @@ impl MetadataClient for HttpMetadataClient {
         // deployments. To enable spoofing of the metadata service, the environment
         // variable GCE_METADATA_HOST is first inspected to decide where metadata
         // requests shall go.
-        let possible_host_override = std::env::var(METADATA_HOST_ENV);
+        let possible_host_override = match &self.host {
+            Some(host) => Ok(host.clone()),
+            None => std::env::var(METADATA_HOST_ENV),
+        };
         let host = possible_host_override.as_deref().unwrap_or({
             // Using 169.254.169.254 instead of "metadata" or "metadata.google.internal" here because
             // we can't know how the user's network is configured.
             METADATA_IP
         });
+        let host = metadata_authority(host)?;
 
-        let suffix = suffix.trim_end_matches('/');
+        // Keep trailing slashes: directory listings such as `instance/` are
+        // only served with them, the metadata server redirects otherwise.
+        // Leading slashes would produce `v1//...` paths, which the metadata
+        // server does not serve.
+        let suffix = suffix.trim_start_matches('/');
         let url = format!("http://{host}/computeMetadata/v1/{suffix}");
         let req = hyper::http::Request::builder()
             .uri(url)
             .header("Metadata-Flavor", "Google")
-            .header("User-Agent", USER_AGENT)
+            .header("User-Agent", &self.user_agent)
             .body(Full::default())
             .map_err(HttpError::from)?;
         // The Go SDK retries this request. We don't do that here. For now.
-        let res = timeout(Duration::from_secs(5), self.client.request(req))
+        let res = timeout(self.timeout, self.client.request(req))
             .await
             .map_err(|_| Error::RequestTimeout)?
             .map_err(HttpError::from)?;
@@ -79,13 +291,16 @@ impl MetadataClient for HttpMetadataClient {
     }
 
     async fn resolve(&self, suffix: &str) -> Result<String, Error> {
-        let (body, _) = self.resolve_etag(suffix).await?;
+        let (body, _) = MetadataClient::resolve_etag(self, suffix).await?;
         Ok(body)
     }
 }
 
+/// Errors returned by a [`MetadataClient`].
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum Error {
+    #[cfg(feature = "client")]
     #[error("HTTP error: {0}")]
     Http(#[from] HttpError),
 
@@ -97,8 +312,83 @@ pub enum Error {
 
     #[error("Request timed out")]
     RequestTimeout,
+
+    #[error("Invalid metadata response: {0}")]
+    InvalidResponse(String),
+
+    #[error("Unsupported metadata host scheme: {0}")]
+    UnsupportedScheme(String),
 }
 
+/// Returns the `host:port` of a metadata host given as `host:port` or as an
+/// `http://` URL.
+///
+/// Emulators such as gce-metadata-server are often configured with a full base
+/// URL rather than a bare `host:port`. The metadata server only speaks plain
+/// HTTP, so other schemes such as `https://` are rejected.
+#[cfg(feature = "client")]
+fn metadata_authority(host: &str) -> Result<&str, Error> {
+    let host = match host.split_once("://") {
+        Some((scheme, rest)) if scheme.eq_ignore_ascii_case("http") => rest,
+        Some((scheme, _)) => return Err(Error::UnsupportedScheme(scheme.to_owned())),
+        None => host,
+    };
+    Ok(host.trim_end_matches('/'))
+}
+
+/// An OAuth 2.0 access token of the instance's default service account.
+///
+/// Returned by [`Detector::access_token`](crate::Detector::access_token).
+/// `Debug` does not print the token itself.
+#[cfg(feature = "token")]
+#[derive(Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct AccessToken {
+    /// The bearer token.
+    pub access_token: String,
+    /// The token type, usually `Bearer`.
+    pub token_type: String,
+    /// How long the token is valid for, counted from when it was fetched.
+    pub expires_in: Duration,
+}
+
+#[cfg(feature = "token")]
+impl AccessToken {
+    /// The path of the default service account's token endpoint.
+    pub(crate) const PATH: &str = "instance/service-accounts/default/token";
+
+    /// Parses the JSON response of the token endpoint.
+    pub(crate) fn parse(body: &str) -> Result<Self, Error> {
+        #[derive(serde::Deserialize)]
+        struct TokenResponse {
+            access_token: String,
+            token_type: String,
+            expires_in: u64,
+        }
+
+        let response: TokenResponse =
+            serde_json::from_str(body).map_err(|err| Error::InvalidResponse(err.to_string()))?;
+        Ok(Self {
+            access_token: response.access_token,
+            token_type: response.token_type,
+            expires_in: Duration::from_secs(response.expires_in),
+        })
+    }
+}
+
+#[cfg(feature = "token")]
+impl std::fmt::Debug for AccessToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AccessToken")
+            .field("access_token", &"<redacted>")
+            .field("token_type", &self.token_type)
+            .field("expires_in", &self.expires_in)
+            .finish()
+    }
+}
+
+/// Transport-level errors of the HTTP metadata client.
+#[cfg(feature = "client")]
 #[derive(Debug, Error)]
 pub enum HttpError {
     #[error("HTTP error: {0}")]
@@ -116,7 +406,8 @@ pub enum HttpError {
 
 /// The documented metadata server IP address.
 ///
-/// See: https://cloud.google.com/compute/docs/metadata/querying-metadata#metadata_server_endpoints
+/// See: <https://cloud.google.com/compute/docs/metadata/querying-metadata#metadata_server_endpoints>
+#[cfg(feature = "client")]
 const METADATA_IP: &str = "169.254.169.254";
 
 /// The environment variable specifying the GCE metadata hostname.
@@ -124,6 +415,87 @@ const METADATA_IP: &str = "169.254.169.254";
 ///
 /// According to the go SDK, this is variable name is not defined by any spec and
 /// was made up for the Go package.
+#[cfg(feature = "client")]
 const METADATA_HOST_ENV: &str = "GCE_METADATA_HOST";
 
-const USER_AGENT: &str = "rust-gcp_metadata_resolver/0.1";
+/// The default `User-Agent` of [`HttpMetadataClient`], including the crate version.
+#[cfg(feature = "client")]
+pub const USER_AGENT: &str = concat!("rust-gcp_metadata_resolver/", env!("CARGO_PKG_VERSION"));
+
+#[cfg(all(test, feature = "client"))]
+mod tests {
+    use super::*;
+
+    use crate::test_util::server::{Reply, TestServer};
+
+    /// Answers requests with their `User-Agent` header, or never if
+    /// `respond` is false.
+    async fn echo_user_agent(respond: bool) -> String {
+        let server = TestServer::start(move |request| match respond {
+            true => Reply::Ok(request.header("user-agent").unwrap_or_default().to_owned()),
+            false => Reply::Never,
+        })
+        .await;
+        server.url()
+    }
+
+    #[tokio::test]
+    async fn builder_sets_host_and_user_agent() {
+        let client = HttpMetadataClient::builder()
+            .host(echo_user_agent(true).await)
+            .user_agent("my-agent/1.0")
+            .build();
+        let body = MetadataClient::resolve(&client, "instance/id")
+            .await
+            .unwrap();
+        assert_eq!(body, "my-agent/1.0");
+
+        let client = HttpMetadataClient::builder()
+            .host(echo_user_agent(true).await)
+            .build();
+        let body = MetadataClient::resolve(&client, "instance/id")
+            .await
+            .unwrap();
+        assert_eq!(body, USER_AGENT);
+    }
+
+    #[test]
+    fn parses_metadata_hosts() {
+        assert_eq!(
+            metadata_authority("localhost:8080").unwrap(),
+            "localhost:8080"
+        );
+        assert_eq!(
+            metadata_authority("HTTP://localhost:8080/").unwrap(),
+            "localhost:8080"
+        );
+        assert!(matches!(
+            metadata_authority("https://localhost:8080"),
+            Err(Error::UnsupportedScheme(scheme)) if scheme == "https"
+        ));
+    }
+
+    #[tokio::test]
+    async fn rejects_https_hosts() {
+        let client = HttpMetadataClient::builder()
+            .host("https://metadata.example")
+            .build();
+        let err = MetadataClient::resolve(&client, "instance/id")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::UnsupportedScheme(_)), "{err:?}");
+    }
+
+    #[tokio::test]
+    async fn builder_sets_request_timeout() {
+        let client = HttpMetadataClient::builder()
+            .host(echo_user_agent(false).await)
+            .request_timeout(Duration::from_millis(50))
+            .connector(HttpConnector::new())
+            .build();
+        let err = MetadataClient::resolve(&client, "instance/id")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::RequestTimeout), "{err:?}");
+    }
+}
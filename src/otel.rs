@@ -0,0 +1,152 @@
+//! Conversions into the OpenTelemetry SDK `Resource`, one per supported SDK version.
+//!
+//! Every conversion goes through [`GcpResourceAttributes::iter`] or
+//! [`DetectedResource::attributes`], so adding a new SDK version only means
+//! adding a feature and a line at the bottom of this file; the conversion
+//! tests are generated alongside.
+use crate::{DetectedResource, GcpResourceAttributes};
+
+macro_rules! impl_resource_conversions {
+    ($feature:literal, $otel:ident, $sdk:ident, $tests:ident) => {
+        #[cfg(feature = $feature)]
+        impl From<&GcpResourceAttributes> for $sdk::Resource {
+            fn from(attrs: &GcpResourceAttributes) -> Self {
+                $sdk::Resource::builder_empty()
+                    .with_attributes(
+                        attrs
+                            .iter()
+                            .map(|(key, value)| $otel::KeyValue::new(key, value.to_owned())),
+                    )
+                    .build()
+            }
+        }
+
+        #[cfg(feature = $feature)]
+        impl From<GcpResourceAttributes> for $sdk::Resource {
+            fn from(attrs: GcpResourceAttributes) -> Self {
+                Self::from(&attrs)
+            }
+        }
+
+        #[cfg(feature = $feature)]
+        impl From<&DetectedResource> for $sdk::Resource {
+            fn from(resource: &DetectedResource) -> Self {
+                $sdk::Resource::builder_empty()
+                    .with_attributes(
+                        resource
+                            .attributes()
+                            .into_iter()
+                            .map(|(key, value)| $otel::KeyValue::new(key, value.to_owned())),
+                    )
+                    .build()
+            }
+        }
+
+        #[cfg(feature = $feature)]
+        impl From<DetectedResource> for $sdk::Resource {
+            fn from(resource: DetectedResource) -> Self {
+                Self::from(&resource)
+            }
+        }
+
+        #[cfg(all(test, feature = $feature))]
+        mod $tests {
+            use $otel::{Key, KeyValue, Value};
+
+            use crate::{DetectedResource, GcpResourceAttributes};
+
+            fn attrs() -> GcpResourceAttributes {
+                GcpResourceAttributes {
+                    cloud_account_id: "my-project".to_owned(),
+                    cloud_platform: Some(crate::CLOUD_PLATFORM_CLOUD_RUN.to_owned()),
+                    cloud_region: Some("us-east1".to_owned()),
+                    cloud_availability_zone: None,
+                    host_id: None,
+                    host_name: None,
+                    host_type: None,
+                    gce_instance_name: None,
+                    gce_instance_hostname: None,
+                    gce_instance_group_manager_name: None,
+                    gce_instance_group_manager_region: None,
+                    gce_instance_group_manager_zone: None,
+                    k8s_cluster_name: None,
+                    faas_name: Some("my-service".to_owned()),
+                    faas_version: None,
+                    faas_instance: None,
+                }
+            }
+
+            #[test]
+            fn resource() {
+                let resource = $sdk::Resource::from(attrs());
+                assert_eq!(resource.len(), 5);
+                assert_eq!(
+                    resource.get(&Key::from_static_str("cloud.provider")),
+                    Some(Value::from("gcp"))
+                );
+                assert_eq!(
+                    resource.get(&Key::from_static_str("cloud.account.id")),
+                    Some(Value::from("my-project"))
+                );
+                assert_eq!(
+                    resource.get(&Key::from_static_str("faas.name")),
+                    Some(Value::from("my-service"))
+                );
+                assert_eq!(resource.get(&Key::from_static_str("faas.version")), None);
+                assert_eq!($sdk::Resource::from(&attrs()), resource);
+            }
+
+            #[test]
+            fn detected_resource() {
+                let resource = $sdk::Resource::from(DetectedResource::ComputeEngine {
+                    project_id: "my-project".to_owned(),
+                    instance_id: Some("1234567891".to_owned()),
+                    zone: None,
+                });
+                assert_eq!(
+                    resource.get(&Key::from_static_str("cloud.platform")),
+                    Some(Value::from("gcp_compute_engine"))
+                );
+                assert_eq!(
+                    resource.get(&Key::from_static_str("host.id")),
+                    Some(Value::from("1234567891"))
+                );
+            }
+
+            #[test]
+            fn inconsistencies_with_resource() {
+                let resource = $sdk::Resource::builder_empty()
+                    .with_attributes([
+                        KeyValue::new("cloud.account.id", "my-project"),
+                        KeyValue::new("cloud.region", "europe-west1"),
+                        KeyValue::new("service.name", "my-service"),
+                    ])
+                    .build();
+                let inconsistencies = attrs().inconsistencies(&resource);
+                assert_eq!(inconsistencies.len(), 1);
+                assert_eq!(inconsistencies[0].key, "cloud.region");
+                assert_eq!(inconsistencies[0].detected, "us-east1");
+                assert_eq!(inconsistencies[0].found, "europe-west1");
+            }
+        }
+    };
+}
+
+impl_resource_conversions!(
+    "opentelemetry_0_29",
+    opentelemetry_0_29,
+    opentelemetry_sdk_0_29,
+    tests_0_29
+);
+impl_resource_conversions!(
+    "opentelemetry_0_30",
+    opentelemetry_0_30,
+    opentelemetry_sdk_0_30,
+    tests_0_30
+);
+impl_resource_conversions!(
+    "opentelemetry_0_31",
+    opentelemetry_0_31,
+    opentelemetry_sdk_0_31,
+    tests_0_31
+);
@@ -0,0 +1,13 @@
+//! Re-exports of the most commonly used types.
+//!
+//! ```
+//! use gcp_metadata_resolver::prelude::*;
+//! ```
+#[cfg(feature = "file-source")]
+pub use crate::FileSourcePriority;
+#[cfg(feature = "client")]
+pub use crate::metadata::HttpMetadataClient;
+pub use crate::metadata::{DynMetadataClient, MetadataClient};
+pub use crate::{DetectError, DetectedResource, GcpResourceAttributes, Region, Zone};
+#[cfg(feature = "detector")]
+pub use crate::{Detector, DetectorBuilder, Diagnostics};
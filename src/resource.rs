@@ -0,0 +1,494 @@
+//! The crate-owned representation of a detected monitored resource.
+use std::fmt;
+
+use crate::location::{split_location, zone_region};
+use crate::{
+    CLOUD_PLATFORM_APP_ENGINE, CLOUD_PLATFORM_CLOUD_FUNCTIONS, CLOUD_PLATFORM_CLOUD_RUN,
+    CLOUD_PLATFORM_COMPUTE_ENGINE, CLOUD_PLATFORM_KUBERNETES_ENGINE, CLOUD_PROVIDER_GCP,
+};
+
+/// The [monitored resource] detected for the current GCP environment.
+///
+/// This is the type detection produces; every integration (the
+/// [`opentelemetry-stackdriver`] `MonitoredResource`, the OpenTelemetry SDK
+/// `Resource`, or a raw `google.api.MonitoredResource` built from
+/// [`resource_type`](Self::resource_type) and [`labels`](Self::labels)) is a
+/// thin `From` conversion on top of it.
+///
+/// # Stability
+///
+/// Unlike the integration types, `DetectedResource` is owned by this crate and
+/// does not change when a downstream crate releases a new version. Existing
+/// variants and fields are only changed in a breaking release of this crate.
+/// The enum is `#[non_exhaustive]` so new platforms can be added in a minor
+/// release.
+///
+/// With the `serde` feature, the resource serializes as an object tagged with
+/// its [`resource_type`](Self::resource_type), with the labels as fields:
+///
+/// ```json
+/// {"type": "cloud_run_revision", "project_id": "my-project", "service_name": "my-service"}
+/// ```
+///
+/// [monitored resource]: https://cloud.google.com/monitoring/api/resources
+/// [`opentelemetry-stackdriver`]: https://crates.io/crates/opentelemetry-stackdriver
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "type")
+)]
+#[non_exhaustive]
+pub enum DetectedResource {
+    /// `gae_app`
+    #[cfg_attr(feature = "serde", serde(rename = "gae_app"))]
+    AppEngine {
+        project_id: String,
+        module_id: Option<String>,
+        version_id: Option<String>,
+        zone: Option<String>,
+    },
+    /// `cloud_function`
+    #[cfg_attr(feature = "serde", serde(rename = "cloud_function"))]
+    CloudFunction {
+        project_id: String,
+        function_name: Option<String>,
+        region: Option<String>,
+    },
+    /// `cloud_run_revision`
+    #[cfg_attr(feature = "serde", serde(rename = "cloud_run_revision"))]
+    CloudRunRevision {
+        project_id: String,
+        service_name: Option<String>,
+        revision_name: Option<String>,
+        location: Option<String>,
+        configuration_name: Option<String>,
+    },
+    /// `cloud_run_job`
+    #[cfg_attr(feature = "serde", serde(rename = "cloud_run_job"))]
+    CloudRunJob {
+        project_id: String,
+        job_name: Option<String>,
+        location: Option<String>,
+    },
+    /// `k8s_container`
+    #[cfg_attr(feature = "serde", serde(rename = "k8s_container"))]
+    KubernetesEngine {
+        project_id: String,
+        location: Option<String>,
+        cluster_name: Option<String>,
+        namespace_name: Option<String>,
+        pod_name: Option<String>,
+        container_name: Option<String>,
+    },
+    /// `gce_instance`
+    #[cfg_attr(feature = "serde", serde(rename = "gce_instance"))]
+    ComputeEngine {
+        project_id: String,
+        instance_id: Option<String>,
+        zone: Option<String>,
+    },
+}
+
+impl DetectedResource {
+    /// Returns the monitored resource type, e.g. `cloud_run_revision`.
+    pub fn resource_type(&self) -> &'static str {
+        match self {
+            Self::AppEngine { .. } => "gae_app",
+            Self::CloudFunction { .. } => "cloud_function",
+            Self::CloudRunRevision { .. } => "cloud_run_revision",
+            Self::CloudRunJob { .. } => "cloud_run_job",
+            Self::KubernetesEngine { .. } => "k8s_container",
+            Self::ComputeEngine { .. } => "gce_instance",
+        }
+    }
+
+    /// Returns the GCP project ID the resource belongs to.
+    pub fn project_id(&self) -> &str {
+        match self {
+            Self::AppEngine { project_id, .. }
+            | Self::CloudFunction { project_id, .. }
+            | Self::CloudRunRevision { project_id, .. }
+            | Self::CloudRunJob { project_id, .. }
+            | Self::KubernetesEngine { project_id, .. }
+            | Self::ComputeEngine { project_id, .. } => project_id,
+        }
+    }
+
+    /// Replaces the project ID of the resource.
+    #[cfg(feature = "detector")]
+    pub(crate) fn set_project_id(&mut self, new_project_id: String) {
+        match self {
+            Self::AppEngine { project_id, .. }
+            | Self::CloudFunction { project_id, .. }
+            | Self::CloudRunRevision { project_id, .. }
+            | Self::CloudRunJob { project_id, .. }
+            | Self::KubernetesEngine { project_id, .. }
+            | Self::ComputeEngine { project_id, .. } => *project_id = new_project_id,
+        }
+    }
+
+    /// Replaces the zone, region, or location of the resource.
+    ///
+    /// Zone-level labels are only set from a zone: a region clears the zone
+    /// of App Engine and Compute Engine resources. Region-level labels take
+    /// the region of a zone.
+    #[cfg(feature = "detector")]
+    pub(crate) fn set_location(&mut self, new_location: String) {
+        let (new_region, new_zone) = split_location(&new_location);
+        match self {
+            Self::AppEngine { zone, .. } | Self::ComputeEngine { zone, .. } => {
+                *zone = new_zone.map(str::to_owned);
+            }
+            Self::CloudFunction { region, .. } => *region = Some(new_region.to_owned()),
+            Self::CloudRunRevision { location, .. } | Self::CloudRunJob { location, .. } => {
+                *location = Some(new_region.to_owned());
+            }
+            Self::KubernetesEngine { location, .. } => *location = Some(new_location),
+        }
+    }
+
+    /// Returns the [`cloud.platform`](https://opentelemetry.io/docs/specs/semconv/attributes-registry/cloud/)
+    /// value for the resource, e.g. `gcp_cloud_run`.
+    pub fn cloud_platform(&self) -> &'static str {
+        match self {
+            Self::AppEngine { .. } => CLOUD_PLATFORM_APP_ENGINE,
+            Self::CloudFunction { .. } => CLOUD_PLATFORM_CLOUD_FUNCTIONS,
+            Self::CloudRunRevision { .. } | Self::CloudRunJob { .. } => CLOUD_PLATFORM_CLOUD_RUN,
+            Self::KubernetesEngine { .. } => CLOUD_PLATFORM_KUBERNETES_ENGINE,
+            Self::ComputeEngine { .. } => CLOUD_PLATFORM_COMPUTE_ENGINE,
+        }
+    }
+
+    /// Returns the monitored resource labels as `(label, value)` pairs.
+    ///
+    /// Label names match the [monitored resource list], so together with
+    /// [`resource_type`](Self::resource_type) this is enough to build a
+    /// `google.api.MonitoredResource`. Unset labels are skipped.
+    ///
+    /// [monitored resource list]: https://cloud.google.com/monitoring/api/resources
+    pub fn labels(&self) -> Vec<(&'static str, &str)> {
+        let labels = match self {
+            Self::AppEngine {
+                project_id,
+                module_id,
+                version_id,
+                zone,
+            } => vec![
+                ("project_id", Some(project_id.as_str())),
+                ("module_id", module_id.as_deref()),
+                ("version_id", version_id.as_deref()),
+                ("zone", zone.as_deref()),
+            ],
+            Self::CloudFunction {
+                project_id,
+                function_name,
+                region,
+            } => vec![
+                ("project_id", Some(project_id.as_str())),
+                ("function_name", function_name.as_deref()),
+                ("region", region.as_deref()),
+            ],
+            Self::CloudRunRevision {
+                project_id,
+                service_name,
+                revision_name,
+                location,
+                configuration_name,
+            } => vec![
+                ("project_id", Some(project_id.as_str())),
+                ("service_name", service_name.as_deref()),
+                ("revision_name", revision_name.as_deref()),
+                ("location", location.as_deref()),
+                ("configuration_name", configuration_name.as_deref()),
+            ],
+            Self::CloudRunJob {
+                project_id,
+                job_name,
+                location,
+            } => vec![
+                ("project_id", Some(project_id.as_str())),
+                ("job_name", job_name.as_deref()),
+                ("location", location.as_deref()),
+            ],
+            Self::KubernetesEngine {
+                project_id,
+                location,
+                cluster_name,
+                namespace_name,
+                pod_name,
+                container_name,
+            } => vec![
+                ("project_id", Some(project_id.as_str())),
+                ("location", location.as_deref()),
+                ("cluster_name", cluster_name.as_deref()),
+                ("namespace_name", namespace_name.as_deref()),
+                ("pod_name", pod_name.as_deref()),
+                ("container_name", container_name.as_deref()),
+            ],
+            Self::ComputeEngine {
+                project_id,
+                instance_id,
+                zone,
+            } => vec![
+                ("project_id", Some(project_id.as_str())),
+                ("instance_id", instance_id.as_deref()),
+                ("zone", zone.as_deref()),
+            ],
+        };
+        labels
+            .into_iter()
+            .filter_map(|(label, value)| value.map(|value| (label, value)))
+            .collect()
+    }
+
+    /// Returns the Cloud Logging log name the platform itself writes a
+    /// workload's standard output to, e.g.
+    /// `projects/my-project/logs/run.googleapis.com%2Fstdout` on Cloud Run.
+    ///
+    /// Log shippers can use it so that their entries end up in the same log
+    /// as those collected by the platform:
+    ///
+    /// | Resource | Log ID |
+    /// |---|---|
+    /// | Cloud Run service or job | `run.googleapis.com/stdout` |
+    /// | Cloud Functions | `cloudfunctions.googleapis.com/cloud-functions` |
+    /// | GKE, App Engine | `stdout` |
+    ///
+    /// Returns `None` on Compute Engine, which has no platform-native log.
+    pub fn suggested_log_name(&self) -> Option<String> {
+        let log_id = match self {
+            Self::CloudRunRevision { .. } | Self::CloudRunJob { .. } => "run.googleapis.com/stdout",
+            Self::CloudFunction { .. } => "cloudfunctions.googleapis.com/cloud-functions",
+            Self::KubernetesEngine { .. } | Self::AppEngine { .. } => "stdout",
+            Self::ComputeEngine { .. } => return None,
+        };
+        Some(format!(
+            "projects/{}/logs/{}",
+            self.project_id(),
+            log_id.replace('/', "%2F")
+        ))
+    }
+
+    /// Returns the resource as [OpenTelemetry semantic convention] attributes.
+    ///
+    /// This only covers what the monitored resource itself carries; use
+    /// [`resource_attributes`](crate::resource_attributes) for the full set of
+    /// host and instance attributes. `cloud.region` and
+    /// `cloud.availability_zone` are derived from the zone or location the same
+    /// way detection derives them, so both agree.
+    ///
+    /// [OpenTelemetry semantic convention]: https://opentelemetry.io/docs/specs/semconv/resource/cloud/
+    pub fn attributes(&self) -> Vec<(&'static str, &str)> {
+        let mut attributes = vec![
+            ("cloud.provider", Some(CLOUD_PROVIDER_GCP)),
+            ("cloud.platform", Some(self.cloud_platform())),
+            ("cloud.account.id", Some(self.project_id())),
+        ];
+        match self {
+            Self::AppEngine {
+                module_id,
+                version_id,
+                zone,
+                ..
+            } => attributes.extend([
+                ("cloud.region", zone.as_deref().and_then(zone_region)),
+                ("cloud.availability_zone", zone.as_deref()),
+                ("faas.name", module_id.as_deref()),
+                ("faas.version", version_id.as_deref()),
+            ]),
+            Self::CloudFunction {
+                function_name,
+                region,
+                ..
+            } => attributes.extend([
+                ("cloud.region", region.as_deref()),
+                ("faas.name", function_name.as_deref()),
+            ]),
+            Self::CloudRunRevision {
+                service_name,
+                revision_name,
+                location,
+                ..
+            } => attributes.extend([
+                ("cloud.region", location.as_deref()),
+                ("faas.name", service_name.as_deref()),
+                ("faas.version", revision_name.as_deref()),
+            ]),
+            Self::CloudRunJob {
+                job_name, location, ..
+            } => attributes.extend([
+                ("cloud.region", location.as_deref()),
+                ("faas.name", job_name.as_deref()),
+            ]),
+            Self::KubernetesEngine {
+                location,
+                cluster_name,
+                namespace_name,
+                pod_name,
+                container_name,
+                ..
+            } => {
+                let (region, zone) = location.as_deref().map(split_location).unzip();
+                attributes.extend([
+                    ("cloud.region", region),
+                    ("cloud.availability_zone", zone.flatten()),
+                    ("k8s.cluster.name", cluster_name.as_deref()),
+                    ("k8s.namespace.name", namespace_name.as_deref()),
+                    ("k8s.pod.name", pod_name.as_deref()),
+                    ("k8s.container.name", container_name.as_deref()),
+                ]);
+            }
+            Self::ComputeEngine {
+                instance_id, zone, ..
+            } => attributes.extend([
+                ("cloud.region", zone.as_deref().and_then(zone_region)),
+                ("cloud.availability_zone", zone.as_deref()),
+                ("host.id", instance_id.as_deref()),
+            ]),
+        }
+        attributes
+            .into_iter()
+            .filter_map(|(key, value)| value.map(|value| (key, value)))
+            .collect()
+    }
+}
+
+/// Formats a one-line summary, e.g.
+/// `cloud_run_revision service=x revision=y location=us-east1 project=z`.
+impl fmt::Display for DetectedResource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.resource_type())?;
+        for (label, value) in self.labels() {
+            if label != "project_id" {
+                let label = label
+                    .strip_suffix("_name")
+                    .or_else(|| label.strip_suffix("_id"))
+                    .unwrap_or(label);
+                write!(f, " {label}={value}")?;
+            }
+        }
+        write!(f, " project={}", self.project_id())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_skip_unset() {
+        let resource = DetectedResource::CloudRunJob {
+            project_id: "my-project".to_owned(),
+            job_name: Some("my-job".to_owned()),
+            location: None,
+        };
+        assert_eq!(resource.resource_type(), "cloud_run_job");
+        assert_eq!(
+            resource.labels(),
+            [("project_id", "my-project"), ("job_name", "my-job")]
+        );
+    }
+
+    #[test]
+    fn suggested_log_names() {
+        let run = DetectedResource::CloudRunJob {
+            project_id: "my-project".to_owned(),
+            job_name: None,
+            location: None,
+        };
+        assert_eq!(
+            run.suggested_log_name().as_deref(),
+            Some("projects/my-project/logs/run.googleapis.com%2Fstdout")
+        );
+        let function = DetectedResource::CloudFunction {
+            project_id: "my-project".to_owned(),
+            function_name: None,
+            region: None,
+        };
+        assert_eq!(
+            function.suggested_log_name().as_deref(),
+            Some("projects/my-project/logs/cloudfunctions.googleapis.com%2Fcloud-functions")
+        );
+        let gce = DetectedResource::ComputeEngine {
+            project_id: "my-project".to_owned(),
+            instance_id: None,
+            zone: None,
+        };
+        assert_eq!(gce.suggested_log_name(), None);
+    }
+
+    #[test]
+    fn display_summary() {
+        let resource = DetectedResource::CloudRunRevision {
+            project_id: "z".to_owned(),
+            service_name: Some("x".to_owned()),
+            revision_name: Some("y".to_owned()),
+            location: None,
+            configuration_name: None,
+        };
+        assert_eq!(
+            resource.to_string(),
+            "cloud_run_revision service=x revision=y project=z"
+        );
+    }
+
+    #[test]
+    fn attributes_gke() {
+        let resource = DetectedResource::KubernetesEngine {
+            project_id: "my-project".to_owned(),
+            location: Some("us-central1".to_owned()),
+            cluster_name: Some("my-cluster".to_owned()),
+            namespace_name: Some("default".to_owned()),
+            pod_name: None,
+            container_name: None,
+        };
+        assert_eq!(
+            resource.attributes(),
+            [
+                ("cloud.provider", "gcp"),
+                ("cloud.platform", "gcp_kubernetes_engine"),
+                ("cloud.account.id", "my-project"),
+                ("cloud.region", "us-central1"),
+                ("k8s.cluster.name", "my-cluster"),
+                ("k8s.namespace.name", "default"),
+            ]
+        );
+    }
+
+    #[test]
+    fn attributes_gke_zonal() {
+        let resource = DetectedResource::KubernetesEngine {
+            project_id: "my-project".to_owned(),
+            location: Some("us-central1-a".to_owned()),
+            cluster_name: None,
+            namespace_name: None,
+            pod_name: None,
+            container_name: None,
+        };
+        assert_eq!(
+            resource.attributes()[3..],
+            [
+                ("cloud.region", "us-central1"),
+                ("cloud.availability_zone", "us-central1-a"),
+            ]
+        );
+    }
+
+    #[test]
+    fn attributes_gce_zone() {
+        let resource = DetectedResource::ComputeEngine {
+            project_id: "my-project".to_owned(),
+            instance_id: Some("1234567891".to_owned()),
+            zone: Some("europe-west1-b".to_owned()),
+        };
+        assert_eq!(
+            resource.attributes()[3..],
+            [
+                ("cloud.region", "europe-west1"),
+                ("cloud.availability_zone", "europe-west1-b"),
+                ("host.id", "1234567891"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,124 @@
+//! Inference of a `service.name`, see [`Detector::service_name`](crate::Detector::service_name).
+use std::sync::LazyLock;
+
+use crate::detection::ResourceAttributesGetter;
+
+/// Used when the environment names no service. Followed by `:` and the
+/// executable name when it is available, like the OpenTelemetry SDK default.
+const UNKNOWN_SERVICE: &str = "unknown_service";
+
+/// Characters Kubernetes uses for generated name suffixes, see
+/// `k8s.io/apimachinery/pkg/util/rand`.
+const POD_SUFFIX_CHARS: &str = "bcdfghjklmnpqrstvwxz2456789";
+
+/// A pod of a Deployment (`<name>-<pod-template-hash>-<suffix>`), or of a
+/// DaemonSet or Job (`<name>-<suffix>`).
+static GENERATED_POD_RE: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(&format!(
+        "^(.+?)(?:-[{POD_SUFFIX_CHARS}]{{6,10}})?-[{POD_SUFFIX_CHARS}]{{5}}$"
+    ))
+    .unwrap()
+});
+
+/// A pod of a StatefulSet (`<name>-<ordinal>`).
+static STATEFUL_POD_RE: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"^(.+)-\d+$").unwrap());
+
+/// Returns the first non-empty of the Cloud Run service or Cloud Functions
+/// name, the App Engine service, the Cloud Run job, the owner of the
+/// Kubernetes pod, and `unknown_service:<executable name>`.
+pub(crate) fn service_name<C>(getter: &ResourceAttributesGetter<C>) -> String {
+    let env = |key: &str| {
+        (getter.env_getter)(key)
+            .ok()
+            .filter(|value| !value.is_empty())
+    };
+    env("K_SERVICE")
+        .or_else(|| env("GAE_SERVICE"))
+        .or_else(|| env("CLOUD_RUN_JOB"))
+        .or_else(|| {
+            // HOSTNAME is the pod name on Kubernetes, but the machine name elsewhere.
+            env("KUBERNETES_SERVICE_HOST")?;
+            env("HOSTNAME").map(|pod| pod_owner(&pod).to_owned())
+        })
+        .unwrap_or_else(|| match executable_name() {
+            Some(exe) => format!("{UNKNOWN_SERVICE}:{exe}"),
+            None => UNKNOWN_SERVICE.to_owned(),
+        })
+}
+
+/// Strips the suffixes Kubernetes controllers append to the names of the
+/// pods they create.
+fn pod_owner(pod: &str) -> &str {
+    [&*GENERATED_POD_RE, &*STATEFUL_POD_RE]
+        .into_iter()
+        .find_map(|re| re.captures(pod))
+        .and_then(|captures| captures.get(1))
+        .map_or(pod, |owner| owner.as_str())
+}
+
+fn executable_name() -> Option<String> {
+    let exe = std::env::current_exe().ok()?;
+    Some(exe.file_stem()?.to_str()?.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::env::VarError;
+
+    use super::*;
+
+    fn service_name_in(vars: &[(&str, &str)]) -> String {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(key, value)| ((*key).to_owned(), (*value).to_owned()))
+            .collect();
+        let getter = ResourceAttributesGetter::new((), move |key| {
+            vars.get(key).cloned().ok_or(VarError::NotPresent)
+        });
+        service_name(&getter)
+    }
+
+    #[test]
+    fn environment_precedence() {
+        let k8s = [
+            ("KUBERNETES_SERVICE_HOST", "10.0.0.1"),
+            ("HOSTNAME", "frontend-7d4b9c8f6d-x2x5z"),
+        ];
+        assert_eq!(service_name_in(&k8s), "frontend");
+        let job = [k8s.as_slice(), &[("CLOUD_RUN_JOB", "my-job")]].concat();
+        assert_eq!(service_name_in(&job), "my-job");
+        let gae = [job.as_slice(), &[("GAE_SERVICE", "default")]].concat();
+        assert_eq!(service_name_in(&gae), "default");
+        let run = [gae.as_slice(), &[("K_SERVICE", "my-service")]].concat();
+        assert_eq!(service_name_in(&run), "my-service");
+        // Empty values are skipped.
+        let empty = [k8s.as_slice(), &[("K_SERVICE", "")]].concat();
+        assert_eq!(service_name_in(&empty), "frontend");
+    }
+
+    #[test]
+    fn hostname_outside_kubernetes_is_ignored() {
+        let name = service_name_in(&[("HOSTNAME", "my-laptop")]);
+        assert_ne!(name, "my-laptop");
+        assert_eq!(
+            name,
+            format!("unknown_service:{}", executable_name().unwrap())
+        );
+    }
+
+    #[test]
+    fn pod_owners() {
+        for (pod, owner) in [
+            ("nginx-deployment-66b6c48dd5-4jw2m", "nginx-deployment"),
+            ("fluentd-xq2vb", "fluentd"),
+            ("web-0", "web"),
+            ("postgres-12", "postgres"),
+            ("standalone", "standalone"),
+            ("my-app", "my-app"),
+        ] {
+            assert_eq!(pod_owner(pod), owner, "{pod}");
+        }
+    }
+}
@@ -0,0 +1,83 @@
+//! Conversions into the [`opentelemetry-stackdriver`](https://crates.io/crates/opentelemetry-stackdriver) types.
+use opentelemetry_stackdriver::MonitoredResource;
+
+use crate::DetectedResource;
+
+impl From<DetectedResource> for MonitoredResource {
+    fn from(resource: DetectedResource) -> Self {
+        match resource {
+            DetectedResource::AppEngine {
+                project_id,
+                module_id,
+                version_id,
+                zone,
+            } => MonitoredResource::AppEngine {
+                project_id,
+                module_id,
+                version_id,
+                zone,
+            },
+            DetectedResource::CloudFunction {
+                project_id,
+                function_name,
+                region,
+            } => MonitoredResource::CloudFunction {
+                project_id,
+                function_name,
+                region,
+            },
+            DetectedResource::CloudRunRevision {
+                project_id,
+                service_name,
+                revision_name,
+                location,
+                configuration_name,
+            } => MonitoredResource::CloudRunRevision {
+                project_id,
+                service_name,
+                revision_name,
+                location,
+                configuration_name,
+            },
+            DetectedResource::CloudRunJob {
+                project_id,
+                job_name,
+                location,
+            } => MonitoredResource::CloudRunJob {
+                project_id,
+                job_name,
+                location,
+            },
+            DetectedResource::KubernetesEngine {
+                project_id,
+                location,
+                cluster_name,
+                namespace_name,
+                pod_name,
+                container_name,
+            } => MonitoredResource::KubernetesEngine {
+                project_id,
+                location,
+                cluster_name,
+                namespace_name,
+                pod_name,
+                container_name,
+            },
+            DetectedResource::ComputeEngine {
+                project_id,
+                instance_id,
+                zone,
+            } => MonitoredResource::ComputeEngine {
+                project_id,
+                instance_id,
+                zone,
+            },
+        }
+    }
+}
+
+impl From<&DetectedResource> for MonitoredResource {
+    fn from(resource: &DetectedResource) -> Self {
+        Self::from(resource.clone())
+    }
+}
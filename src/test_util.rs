@@ -0,0 +1,40 @@
+//! Helpers for testing code that uses this crate.
+//!
+//! [`FakeMetadataClient`] answers metadata requests from a fixed map, and the
+//! [`strategies`] module generates such maps and environment snapshots for
+//! property tests. [`ENV_VARS`] and [`METADATA_PATHS`] list the environment
+//! variables and metadata paths detection reads, e.g. to clear them or to
+//! build environments by hand.
+//!
+//! With the `test-util-emulator` feature, `MetadataEmulator` runs the
+//! [gce-metadata-server] emulator in a Docker container via [testcontainers],
+//! so detection and token flows can be tested end to end:
+//!
+//! ```no_run
+//! # #[cfg(feature = "test-util-emulator")]
+//! # async fn test() -> Result<(), testcontainers::TestcontainersError> {
+//! use gcp_metadata_resolver::test_util::MetadataEmulator;
+//!
+//! let emulator = MetadataEmulator::start().await?;
+//! let detector = emulator.detector_builder().build();
+//! assert_eq!(detector.project_id().await.as_deref(), Some(MetadataEmulator::PROJECT_ID));
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [gce-metadata-server]: https://github.com/salrashid123/gce_metadata_server
+//! [testcontainers]: https://crates.io/crates/testcontainers
+#[cfg(feature = "test-util-emulator")]
+mod emulator;
+mod fake;
+#[cfg(all(test, feature = "client"))]
+#[cfg_attr(not(feature = "detector"), expect(dead_code))]
+pub(crate) mod server;
+#[cfg(feature = "test-util")]
+pub mod strategies;
+
+#[cfg(feature = "test-util")]
+pub use crate::detection::{ENV_VARS, METADATA_PATHS};
+#[cfg(feature = "test-util-emulator")]
+pub use emulator::{MetadataEmulator, MetadataEmulatorBuilder};
+pub use fake::FakeMetadataClient;
@@ -0,0 +1,217 @@
+//! A gce-metadata-server emulator container, see [`MetadataEmulator`].
+use std::env;
+use std::ffi::OsString;
+use std::time::{Duration, Instant};
+
+use testcontainers::core::{IntoContainerPort, WaitFor};
+use testcontainers::runners::AsyncRunner;
+use testcontainers::{ContainerAsync, GenericImage, ImageExt, TestcontainersError};
+
+use crate::metadata::{HttpMetadataClient, MetadataClient};
+use crate::{Detector, DetectorBuilder};
+
+const IMAGE: &str = "salrashid123/gcemetadataserver";
+/// The image tag the emulator is tested against, so new releases cannot
+/// break tests unnoticed.
+const TAG: &str = "v3.4.0";
+const PORT: u16 = 8080;
+const METADATA_HOST_ENV: &str = "GCE_METADATA_HOST";
+/// How long to wait for the emulator to answer its first request.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The emulator configuration used unless [`MetadataEmulatorBuilder::config_json`] is set.
+///
+/// CI starts the emulator with the same file for `tests/emulator.rs`.
+const DEFAULT_CONFIG: &str = include_str!("emulator-config.json");
+
+/// A running gce-metadata-server emulator container.
+///
+/// The container is stopped and removed when the emulator is dropped.
+pub struct MetadataEmulator {
+    container: ContainerAsync<GenericImage>,
+    host: String,
+    /// The `GCE_METADATA_HOST` value to restore on drop, if it was replaced.
+    previous_host_env: Option<Option<OsString>>,
+}
+
+impl MetadataEmulator {
+    /// The project ID reported by the default configuration.
+    pub const PROJECT_ID: &str = "emulated-project";
+
+    /// Starts the emulator with the default configuration and no service
+    /// account, so token requests fail.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if Docker is unavailable or the emulator does not
+    /// start answering requests within 30 seconds.
+    pub async fn start() -> Result<Self, TestcontainersError> {
+        Self::builder().start().await
+    }
+
+    /// Returns a builder for a customized emulator.
+    pub fn builder() -> MetadataEmulatorBuilder {
+        MetadataEmulatorBuilder::default()
+    }
+
+    /// Returns the emulator's `host:port`.
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// Returns a [`DetectorBuilder`] pointed at the emulator.
+    ///
+    /// The builder uses an empty environment, so the emulated Compute Engine
+    /// instance is detected regardless of the test's own environment.
+    pub fn detector_builder(&self) -> DetectorBuilder {
+        let env: [(&str, &str); 0] = [];
+        Detector::builder().metadata_host(&self.host).env(env)
+    }
+
+    /// Points `GCE_METADATA_HOST` at the emulator, for code that uses the
+    /// global detector or other metadata clients. The previous value is
+    /// restored when the emulator is dropped.
+    ///
+    /// # Safety
+    ///
+    /// No other thread may read or write the process environment from this
+    /// call until the emulator is dropped, see [`std::env::set_var`].
+    pub unsafe fn set_metadata_host_env(&mut self) {
+        if self.previous_host_env.is_none() {
+            self.previous_host_env = Some(env::var_os(METADATA_HOST_ENV));
+        }
+        // SAFETY: upheld by the caller.
+        unsafe { env::set_var(METADATA_HOST_ENV, &self.host) };
+    }
+
+    /// Returns the underlying container, e.g. to read its logs.
+    pub fn container(&self) -> &ContainerAsync<GenericImage> {
+        &self.container
+    }
+
+    async fn wait_until_ready(&self) -> Result<(), TestcontainersError> {
+        // A plain client, as the detector logs every failed attempt as an error.
+        let client = HttpMetadataClient::builder()
+            .host(&self.host)
+            .request_timeout(Duration::from_secs(1))
+            .build();
+        let start = Instant::now();
+        while client.resolve("project/project-id").await.is_err() {
+            if start.elapsed() > STARTUP_TIMEOUT {
+                return Err(TestcontainersError::other(
+                    "metadata emulator did not become ready",
+                ));
+            }
+            tokio::time::sleep(Duration::from_millis(250)).await;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for MetadataEmulator {
+    fn drop(&mut self) {
+        if let Some(previous) = self.previous_host_env.take() {
+            // SAFETY: the caller of `set_metadata_host_env` guaranteed
+            // exclusive access to the environment until now.
+            unsafe {
+                match previous {
+                    Some(value) => env::set_var(METADATA_HOST_ENV, value),
+                    None => env::remove_var(METADATA_HOST_ENV),
+                }
+            }
+        }
+    }
+}
+
+/// Builder for a [`MetadataEmulator`].
+#[derive(Debug, Clone)]
+pub struct MetadataEmulatorBuilder {
+    tag: String,
+    config: Vec<u8>,
+    service_account: Option<Vec<u8>>,
+}
+
+impl MetadataEmulatorBuilder {
+    /// Sets the image tag, overriding the pinned release this crate is tested
+    /// against, e.g. `latest` to try a newer emulator.
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tag = tag.into();
+        self
+    }
+
+    /// Replaces the emulator's metadata configuration, in the emulator's
+    /// `config.json` format.
+    pub fn config_json(mut self, config: impl Into<Vec<u8>>) -> Self {
+        self.config = config.into();
+        self
+    }
+
+    /// Uses a service account key file to mint access tokens.
+    pub fn service_account_json(mut self, key: impl Into<Vec<u8>>) -> Self {
+        self.service_account = Some(key.into());
+        self
+    }
+
+    /// Starts the emulator and waits until it answers metadata requests.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if Docker is unavailable or the emulator does not
+    /// start answering requests within 30 seconds.
+    pub async fn start(self) -> Result<MetadataEmulator, TestcontainersError> {
+        let mut cmd = vec![
+            "--configFile=/config.json".to_owned(),
+            "--interface=0.0.0.0".to_owned(),
+            format!("--port=:{PORT}"),
+        ];
+        let image = GenericImage::new(IMAGE, &self.tag)
+            .with_exposed_port(PORT.tcp())
+            .with_wait_for(WaitFor::Nothing);
+        let mut request = image.with_copy_to("/config.json", self.config);
+        if let Some(key) = self.service_account {
+            cmd.push("--serviceAccountFile=/service-account.json".to_owned());
+            request = request.with_copy_to("/service-account.json", key);
+        }
+        let container = request.with_cmd(cmd).start().await?;
+        let host = format!(
+            "{}:{}",
+            container.get_host().await?,
+            container.get_host_port_ipv4(PORT).await?
+        );
+        let emulator = MetadataEmulator {
+            container,
+            host,
+            previous_host_env: None,
+        };
+        emulator.wait_until_ready().await?;
+        Ok(emulator)
+    }
+}
+
+impl Default for MetadataEmulatorBuilder {
+    fn default() -> Self {
+        Self {
+            tag: TAG.to_owned(),
+            config: DEFAULT_CONFIG.into(),
+            service_account: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_config_is_compute_engine() {
+        let config: serde_json::Value = serde_json::from_str(DEFAULT_CONFIG).unwrap();
+        let instance = &config["computeMetadata"]["v1"]["instance"];
+        // Detection only reports Compute Engine if both are non-empty.
+        for key in ["preempted", "cpuPlatform"] {
+            assert!(
+                !instance[key].as_str().unwrap_or_default().is_empty(),
+                "{key}"
+            );
+        }
+    }
+}
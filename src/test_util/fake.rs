@@ -0,0 +1,64 @@
+//! A metadata client answering from a fixed map, see [`FakeMetadataClient`].
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::metadata::{Error, MetadataClient};
+
+/// A [`MetadataClient`] answering from a fixed map of paths to values.
+///
+/// Paths are relative to `computeMetadata/v1/`, e.g. `instance/zone`. Paths
+/// not in the map are reported as [`Error::NotDefined`], like the metadata
+/// server does for keys that do not apply to the platform.
+///
+/// ```
+/// use std::sync::Arc;
+///
+/// use gcp_metadata_resolver::Detector;
+/// use gcp_metadata_resolver::test_util::FakeMetadataClient;
+///
+/// let client = FakeMetadataClient::new([
+///     ("", "instance/\nproject/\n"),
+///     ("project/project-id", "my-project"),
+///     ("instance/id", "1234567891"),
+///     ("instance/zone", "projects/123/zones/us-central1-a"),
+/// ]);
+/// let detector = Detector::builder()
+///     .metadata_client(Arc::new(client))
+///     .env([("HOSTNAME", "my-vm")])
+///     .build();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FakeMetadataClient {
+    values: Arc<HashMap<String, String>>,
+}
+
+impl FakeMetadataClient {
+    /// Returns a client serving `values`.
+    pub fn new<K, V>(values: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let values = values
+            .into_iter()
+            .map(|(path, value)| (path.into(), value.into()))
+            .collect();
+        Self {
+            values: Arc::new(values),
+        }
+    }
+}
+
+impl MetadataClient for FakeMetadataClient {
+    async fn resolve_etag(&self, suffix: &str) -> Result<(String, Option<String>), Error> {
+        let value = self.resolve(suffix).await?;
+        Ok((value, None))
+    }
+
+    async fn resolve(&self, suffix: &str) -> Result<String, Error> {
+        self.values
+            .get(suffix)
+            .cloned()
+            .ok_or_else(|| Error::NotDefined(suffix.to_owned()))
+    }
+}
@@ -0,0 +1,133 @@
+//! A minimal HTTP/1.1 server for tests that need a real connection, see
+//! [`TestServer`].
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// A request received by a [`TestServer`].
+pub(crate) struct Request<'a> {
+    /// The path relative to `computeMetadata/v1/`, e.g. `instance/id`.
+    pub(crate) suffix: &'a str,
+    head: &'a str,
+}
+
+impl Request<'_> {
+    /// Returns the value of the header `name`, compared case-insensitively.
+    pub(crate) fn header(&self, name: &str) -> Option<&str> {
+        self.head.lines().skip(1).find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.eq_ignore_ascii_case(name).then(|| value.trim())
+        })
+    }
+}
+
+/// How a [`TestServer`] answers a request.
+pub(crate) enum Reply {
+    /// `200 OK` with the body.
+    Ok(String),
+    /// `404 Not Found`, like the metadata server for undefined keys.
+    NotFound,
+    /// Keeps the connection open without ever answering.
+    Never,
+}
+
+/// Serves requests over keep-alive connections with a handler and counts
+/// the requests it received.
+pub(crate) struct TestServer {
+    addr: SocketAddr,
+    requests: Arc<AtomicUsize>,
+}
+
+impl TestServer {
+    /// Starts a server answering every request with `handler`.
+    pub(crate) async fn start<F>(handler: F) -> Self
+    where
+        F: Fn(Request<'_>) -> Reply + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handler = Arc::new(handler);
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(serve_connection(stream, handler.clone(), counter.clone()));
+            }
+        });
+        Self { addr, requests }
+    }
+
+    /// Starts a server answering from a fixed map of paths to values, and
+    /// with `404 Not Found` for other paths.
+    pub(crate) async fn with_values(values: &[(&'static str, &'static str)]) -> Self {
+        let values: HashMap<&str, &str> = values.iter().copied().collect();
+        Self::start(move |request| match values.get(request.suffix) {
+            Some(body) => Reply::Ok((*body).to_owned()),
+            None => Reply::NotFound,
+        })
+        .await
+    }
+
+    /// Returns the address the server listens on.
+    pub(crate) fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Returns the server's URL, e.g. `http://127.0.0.1:1234/`.
+    pub(crate) fn url(&self) -> String {
+        format!("http://{}/", self.addr)
+    }
+
+    /// Returns how many requests the server received.
+    #[cfg_attr(
+        not(any(
+            feature = "integrations",
+            feature = "file-cache",
+            feature = "file-source"
+        )),
+        expect(dead_code)
+    )]
+    pub(crate) fn requests(&self) -> usize {
+        self.requests.load(Ordering::SeqCst)
+    }
+}
+
+async fn serve_connection<F>(mut stream: TcpStream, handler: Arc<F>, counter: Arc<AtomicUsize>)
+where
+    F: Fn(Request<'_>) -> Reply,
+{
+    let mut buf = Vec::new();
+    let mut chunk = [0; 1024];
+    loop {
+        while let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            let head = String::from_utf8_lossy(&buf[..end]).into_owned();
+            buf.drain(..end + 4);
+            counter.fetch_add(1, Ordering::SeqCst);
+            let path = head.split(' ').nth(1).unwrap_or_default();
+            let request = Request {
+                suffix: path.trim_start_matches("/computeMetadata/v1/"),
+                head: &head,
+            };
+            let response = match handler(request) {
+                Reply::Ok(body) => format!(
+                    "HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\r\n{body}",
+                    body.len()
+                ),
+                Reply::NotFound => "HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\n\r\n".to_owned(),
+                Reply::Never => std::future::pending().await,
+            };
+            if stream.write_all(response.as_bytes()).await.is_err() {
+                return;
+            }
+        }
+        match stream.read(&mut chunk).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => buf.extend_from_slice(&chunk[..n]),
+        }
+    }
+}
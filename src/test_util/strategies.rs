@@ -0,0 +1,213 @@
+//! [proptest] strategies for metadata maps and environment snapshots.
+//!
+//! Generated values are mostly well-formed (zones, regions, instance group
+//! managers, ...) with a share of arbitrary strings mixed in, so properties
+//! cover both realistic environments and malformed responses.
+//!
+//! ```
+//! use gcp_metadata_resolver::test_util::strategies;
+//! use proptest::prelude::*;
+//!
+//! // Detection never panics.
+//! proptest!(|(snapshot in strategies::snapshot())| {
+//!     let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+//!     let _ = runtime.block_on(snapshot.detector().detect());
+//! });
+//! ```
+//!
+//! [proptest]: https://crates.io/crates/proptest
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use proptest::prelude::*;
+
+use super::{ENV_VARS, FakeMetadataClient};
+use crate::{Detector, DetectorBuilder};
+
+/// A generated environment: metadata server values and environment variables.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Snapshot {
+    /// Metadata values by path, relative to `computeMetadata/v1/`.
+    pub metadata: HashMap<String, String>,
+    /// Environment variables.
+    pub env: HashMap<String, String>,
+}
+
+impl Snapshot {
+    /// Returns a [`DetectorBuilder`] serving this snapshot from a
+    /// [`FakeMetadataClient`] and a fixed environment.
+    pub fn detector_builder(&self) -> DetectorBuilder {
+        let client = FakeMetadataClient::new(self.metadata.clone());
+        Detector::builder()
+            .metadata_client(Arc::new(client))
+            .env(self.env.clone())
+    }
+
+    /// Returns a detector serving this snapshot.
+    pub fn detector(&self) -> Detector {
+        self.detector_builder().build()
+    }
+}
+
+/// Generates a [`Snapshot`] of a metadata map and an environment.
+pub fn snapshot() -> impl Strategy<Value = Snapshot> {
+    (metadata_map(), env_snapshot()).prop_map(|(metadata, env)| Snapshot { metadata, env })
+}
+
+/// Generates a subset of the metadata paths read during detection, with
+/// values for each.
+///
+/// Most paths are present in most maps. The GKE cluster attributes are in
+/// about half of them, and `gae_app_bucket`, which only exists on App Engine
+/// flexible, is rare.
+pub fn metadata_map() -> impl Strategy<Value = HashMap<String, String>> {
+    let number = || "[0-9]{1,12}";
+    let paths: Vec<(&'static str, BoxedStrategy<String>)> = vec![
+        ("", Just("instance/\nproject/\n".to_owned()).boxed()),
+        ("instance/attributes/created-by", created_by().boxed()),
+        ("instance/cpu-platform", "Intel [A-Z][a-z]{3,10}".boxed()),
+        (
+            "instance/hostname",
+            "[a-z]{1,10}\\.c\\.[a-z]{1,10}\\.internal".boxed(),
+        ),
+        ("instance/id", "[0-9]{1,19}".boxed()),
+        (
+            "instance/machine-type",
+            (number(), "[a-z0-9]{1,4}-[a-z]{1,8}")
+                .prop_map(|(project, machine)| format!("projects/{project}/machineTypes/{machine}"))
+                .boxed(),
+        ),
+        ("instance/name", name().boxed()),
+        ("instance/preempted", prop_oneof!["TRUE", "FALSE"].boxed()),
+        (
+            "instance/region",
+            (number(), region())
+                .prop_map(|(project, region)| format!("projects/{project}/regions/{region}"))
+                .boxed(),
+        ),
+        (
+            "instance/zone",
+            (number(), zone())
+                .prop_map(|(project, zone)| format!("projects/{project}/zones/{zone}"))
+                .boxed(),
+        ),
+        ("project/project-id", project_id().boxed()),
+    ];
+    let mut entries = subset(paths, 0.9);
+    entries.extend(subset(
+        [
+            ("instance/attributes/cluster-name", name().boxed()),
+            (
+                "instance/attributes/cluster-location",
+                prop_oneof![zone(), region()].boxed(),
+            ),
+        ],
+        0.5,
+    ));
+    entries.extend(subset(
+        [("instance/attributes/gae_app_bucket", name().boxed())],
+        0.1,
+    ));
+    merge(entries)
+}
+
+/// Generates the environment variables of a randomly chosen platform
+/// (Compute Engine or GKE, Cloud Run, Cloud Run jobs, Cloud Functions, App
+/// Engine), plus a few unrelated ones.
+pub fn env_snapshot() -> impl Strategy<Value = HashMap<String, String>> {
+    prop_oneof![
+        platform_env(&[]),
+        platform_env(&["K_SERVICE", "K_CONFIGURATION", "K_REVISION"]),
+        platform_env(&["CLOUD_RUN_JOB"]),
+        platform_env(&["FUNCTION_TARGET", "K_SERVICE"]),
+        platform_env(&["GAE_SERVICE", "GAE_VERSION", "GAE_INSTANCE"]),
+    ]
+}
+
+/// Sets every variable in `required` and a few of the others.
+fn platform_env(required: &[&'static str]) -> impl Strategy<Value = HashMap<String, String>> {
+    let (required, optional) = ENV_VARS
+        .into_iter()
+        .partition::<Vec<_>, _>(|var| required.contains(var));
+    let mut entries = all(required.into_iter().map(|var| (var, name().boxed())));
+    entries.extend(subset(
+        optional.into_iter().map(|var| (var, name().boxed())),
+        0.1,
+    ));
+    merge(entries)
+}
+
+/// Generates a zone, e.g. `us-central1-a`.
+pub fn zone() -> impl Strategy<Value = String> {
+    (region(), "[a-f]").prop_map(|(region, zone)| format!("{region}-{zone}"))
+}
+
+/// Generates a region, e.g. `us-central1`.
+pub fn region() -> impl Strategy<Value = String> {
+    "[a-z]{2,12}-[a-z]{2,12}[0-9]{1,2}"
+}
+
+/// Generates a project ID, e.g. `my-project-123`.
+pub fn project_id() -> impl Strategy<Value = String> {
+    "[a-z][a-z0-9-]{4,28}[a-z0-9]"
+}
+
+/// Generates a resource name, e.g. a service or cluster name.
+pub fn name() -> impl Strategy<Value = String> {
+    "[a-z][a-z0-9-]{0,30}"
+}
+
+/// Generates a `created-by` instance attribute, the instance group manager
+/// of an instance in a managed instance group.
+fn created_by() -> impl Strategy<Value = String> {
+    (
+        "[0-9]{1,12}",
+        prop_oneof![
+            zone().prop_map(|zone| format!("zones/{zone}")),
+            region().prop_map(|region| format!("regions/{region}")),
+        ],
+        name(),
+    )
+        .prop_map(|(project, location, name)| {
+            format!("projects/{project}/{location}/instanceGroupManagers/{name}")
+        })
+}
+
+type Entry = BoxedStrategy<Option<(String, String)>>;
+
+/// Includes each of `entries` with `probability`, occasionally replacing a
+/// well-formed value with an arbitrary string.
+fn subset(
+    entries: impl IntoIterator<Item = (&'static str, BoxedStrategy<String>)>,
+    probability: f64,
+) -> Vec<Entry> {
+    entries
+        .into_iter()
+        .map(|(key, value)| {
+            proptest::option::weighted(probability, mostly_valid(value))
+                .prop_map(move |value| value.map(|value| (key.to_owned(), value)))
+                .boxed()
+        })
+        .collect()
+}
+
+/// Like [`subset`], but includes every entry.
+fn all(entries: impl IntoIterator<Item = (&'static str, BoxedStrategy<String>)>) -> Vec<Entry> {
+    entries
+        .into_iter()
+        .map(|(key, value)| {
+            mostly_valid(value)
+                .prop_map(move |value| Some((key.to_owned(), value)))
+                .boxed()
+        })
+        .collect()
+}
+
+fn mostly_valid(value: BoxedStrategy<String>) -> impl Strategy<Value = String> {
+    prop_oneof![9 => value, 1 => any::<String>()]
+}
+
+fn merge(entries: Vec<Entry>) -> impl Strategy<Value = HashMap<String, String>> {
+    entries.prop_map(|entries| entries.into_iter().flatten().collect())
+}
@@ -0,0 +1,81 @@
+//! End-to-end tests against the [gce-metadata-server] emulator.
+//!
+//! Ignored by default, as they need `GCE_METADATA_EMULATOR_HOST` set to the
+//! emulator's `host:port`. Start the `salrashid123/gcemetadataserver` image
+//! with `src/test_util/emulator-config.json`, as the `emulator-host` CI job
+//! does (the token test additionally needs service account credentials), then
+//! run
+//!
+//! ```sh
+//! GCE_METADATA_EMULATOR_HOST=localhost:8080 cargo test --all-features --test emulator -- --ignored --skip testcontainers
+//! ```
+//!
+//! [gce-metadata-server]: https://github.com/salrashid123/gce_metadata_server
+#![cfg(feature = "detector")]
+use gcp_metadata_resolver::{DetectedResource, Detector};
+
+fn emulator_detector() -> Detector {
+    let host =
+        std::env::var("GCE_METADATA_EMULATOR_HOST").expect("GCE_METADATA_EMULATOR_HOST is not set");
+    // Only the metadata server decides the platform, not the test's environment.
+    let env: [(&str, &str); 0] = [];
+    Detector::builder().metadata_host(host).env(env).build()
+}
+
+#[tokio::test]
+#[ignore = "requires GCE_METADATA_EMULATOR_HOST"]
+async fn detects_compute_engine() {
+    let detector = emulator_detector();
+    let resource = detector.detect().await.unwrap();
+    assert!(
+        matches!(
+            resource,
+            DetectedResource::ComputeEngine {
+                instance_id: Some(_),
+                zone: Some(_),
+                ..
+            }
+        ),
+        "{resource:?}"
+    );
+    assert!(!resource.project_id().is_empty());
+    assert_eq!(
+        detector.project_id().await.as_deref(),
+        Some(resource.project_id())
+    );
+}
+
+#[tokio::test]
+#[ignore = "requires GCE_METADATA_EMULATOR_HOST"]
+async fn resource_attributes() {
+    let detector = emulator_detector();
+    let attributes = detector.resource_attributes().await.unwrap();
+    assert_eq!(
+        attributes.cloud_platform.as_deref(),
+        Some("gcp_compute_engine")
+    );
+    assert!(attributes.host_id.is_some());
+}
+
+#[cfg(feature = "token")]
+#[tokio::test]
+#[ignore = "requires GCE_METADATA_EMULATOR_HOST"]
+async fn access_token() {
+    let detector = emulator_detector();
+    let token = detector.access_token().await.unwrap();
+    assert!(!token.access_token.is_empty());
+    assert_eq!(token.token_type, "Bearer");
+}
+
+#[cfg(feature = "test-util-emulator")]
+#[tokio::test]
+#[ignore = "requires Docker"]
+async fn testcontainers_emulator() {
+    use gcp_metadata_resolver::test_util::MetadataEmulator;
+
+    let emulator = MetadataEmulator::start().await.unwrap();
+    let detector = emulator.detector_builder().build();
+    let resource = detector.detect().await.unwrap();
+    assert_eq!(resource.project_id(), MetadataEmulator::PROJECT_ID);
+    assert_eq!(resource.resource_type(), "gce_instance");
+}
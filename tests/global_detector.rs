@@ -0,0 +1,60 @@
+//! The free functions against an installed global detector.
+//!
+//! The global detector is process-wide, so everything runs in a single test.
+#![cfg(feature = "test-util")]
+use std::sync::Arc;
+
+use gcp_metadata_resolver::test_util::FakeMetadataClient;
+use gcp_metadata_resolver::{DetectedResource, Detector};
+
+#[tokio::test]
+async fn free_functions_use_installed_detector() {
+    // Peeking must not create a default global detector.
+    assert!(gcp_metadata_resolver::try_detect().is_none());
+    #[cfg(feature = "integrations")]
+    assert!(gcp_metadata_resolver::try_detected_resource().is_none());
+    assert!(gcp_metadata_resolver::diagnostics().is_none());
+
+    let client = FakeMetadataClient::new([
+        ("", "ok"),
+        ("project/project-id", "installed-project"),
+        ("instance/id", "1234567891"),
+        ("instance/zone", "projects/123/zones/europe-west1-b"),
+        ("instance/preempted", "FALSE"),
+        ("instance/cpu-platform", "Intel Broadwell"),
+    ]);
+    let detector = Detector::builder()
+        .metadata_client(Arc::new(client))
+        .env([("HOSTNAME", "my-vm")])
+        .build();
+    gcp_metadata_resolver::set_global_detector(detector).unwrap();
+
+    let resource = gcp_metadata_resolver::detect().await.unwrap();
+    assert_eq!(
+        resource,
+        &DetectedResource::ComputeEngine {
+            project_id: "installed-project".to_owned(),
+            instance_id: Some("1234567891".to_owned()),
+            zone: Some("europe-west1-b".to_owned()),
+        }
+    );
+    assert_eq!(
+        gcp_metadata_resolver::project_id().await.as_deref(),
+        Some("installed-project")
+    );
+    assert_eq!(
+        gcp_metadata_resolver::instance_id().await.as_deref(),
+        Some("1234567891")
+    );
+    let attributes = gcp_metadata_resolver::resource_attributes().await.unwrap();
+    assert_eq!(attributes.cloud_region.as_deref(), Some("europe-west1"));
+    assert_eq!(
+        attributes.cloud_platform.as_deref(),
+        Some("gcp_compute_engine")
+    );
+
+    assert_eq!(gcp_metadata_resolver::try_detect(), Some(resource));
+    #[cfg(feature = "integrations")]
+    assert!(gcp_metadata_resolver::try_detected_resource().is_some());
+    assert!(gcp_metadata_resolver::diagnostics().is_some());
+}
@@ -0,0 +1,53 @@
+//! Property tests of detection against generated environments.
+#![cfg(feature = "test-util")]
+use std::collections::HashMap;
+
+use gcp_metadata_resolver::test_util::strategies::{self, Snapshot};
+use gcp_metadata_resolver::{DetectedResource, GcpResourceAttributes, Region, Zone};
+use proptest::prelude::*;
+
+fn detect(snapshot: &Snapshot) -> (Option<DetectedResource>, Option<GcpResourceAttributes>) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let detector = snapshot.detector();
+    runtime.block_on(async {
+        let resource = detector.detect().await.ok().cloned();
+        let attributes = detector.resource_attributes().await.cloned();
+        (resource, attributes)
+    })
+}
+
+proptest! {
+    #[test]
+    fn detection_is_deterministic(snapshot in strategies::snapshot()) {
+        let (resource, _) = detect(&snapshot);
+        let (again, _) = detect(&snapshot);
+        prop_assert_eq!(resource, again);
+    }
+
+    #[test]
+    fn resource_and_attributes_agree(snapshot in strategies::snapshot()) {
+        let (resource, attributes) = detect(&snapshot);
+        if let (Some(resource), Some(attributes)) = (resource, attributes) {
+            prop_assert_eq!(resource.project_id(), attributes.cloud_account_id.as_str());
+            prop_assert_eq!(Some(resource.cloud_platform()), attributes.cloud_platform.as_deref());
+            let expected: HashMap<_, _> = attributes.iter().collect();
+            for (key, value) in resource.attributes() {
+                // The resource carries Kubernetes workload names that only the
+                // attributes of the SDK's own detectors provide.
+                if !key.starts_with("k8s.") || key == "k8s.cluster.name" {
+                    prop_assert_eq!(Some(&value), expected.get(key), "{}", key);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn generated_locations_parse(zone in strategies::zone(), region in strategies::region()) {
+        let parsed: Zone = zone.parse().unwrap();
+        prop_assert!(zone.starts_with(parsed.region().as_str()));
+        prop_assert!(parsed.region().as_str().parse::<Region>().is_ok());
+        prop_assert_eq!(region.parse::<Region>().unwrap().to_string(), region);
+    }
+}